@@ -0,0 +1,213 @@
+//! Matrices of bits, for column statistics.
+
+use std::str::FromStr;
+
+/// Which bit to pick in a column.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Commonness {
+    Most,
+    Least,
+}
+
+/// Which bit to pick when a column has as many ones as zeros.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TieBreak {
+    One,
+    Zero,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ParseError {
+    /// Rows must have between 1 and 64 columns.
+    BadWidth(usize),
+    /// All rows must have the same width as the first one.
+    Ragged {
+        row: usize,
+        width: usize,
+    },
+    NotABit(char),
+}
+
+/// A matrix of bits, each row stored as a number.  Columns are
+/// numbered from the left, that is, from the most significant bit.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BitMatrix {
+    rows: Vec<u64>,
+    width: usize,
+}
+
+impl BitMatrix {
+    pub fn new(width: usize) -> BitMatrix {
+        assert!((1..=64).contains(&width), "Bad width {}", width);
+        BitMatrix {
+            rows: vec![],
+            width,
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn rows(&self) -> &[u64] {
+        &self.rows
+    }
+
+    pub fn push(&mut self, row: u64) {
+        self.rows.push(row & self.mask());
+    }
+
+    /// All the bits of a row.
+    fn mask(&self) -> u64 {
+        u64::MAX >> (64 - self.width)
+    }
+
+    fn column_bit(&self, col: usize) -> u64 {
+        1 << (self.width - 1 - col)
+    }
+
+    /// The number of ones in column `col`.
+    pub fn count_ones(&self, col: usize) -> usize {
+        let bit = self.column_bit(col);
+        self.rows.iter().filter(|r| *r & bit != 0).count()
+    }
+
+    /// The number of ones in each column.
+    pub fn column_ones(&self) -> Vec<usize> {
+        (0..self.width).map(|col| self.count_ones(col)).collect()
+    }
+
+    /// The most or least common bit in column `col`, as a bool.
+    pub fn common_bit(&self, col: usize, which: Commonness, tie: TieBreak) -> bool {
+        let ones = self.count_ones(col) * 2;
+        let len = self.rows.len();
+        if ones == len {
+            tie == TieBreak::One
+        } else {
+            (ones > len) == (which == Commonness::Most)
+        }
+    }
+
+    /// The number made of the most or least common bit of each
+    /// column.
+    pub fn common_bits(&self, which: Commonness, tie: TieBreak) -> u64 {
+        (0..self.width)
+            .filter(|col| self.common_bit(*col, which, tie))
+            .fold(0, |acc, col| acc | self.column_bit(col))
+    }
+
+    /// Keep the rows whose bit in the first column is the most or
+    /// least common one, then do the same with the remaining rows
+    /// for the next column, and so on until a single row remains.
+    /// Columns where all remaining rows agree are skipped, rather
+    /// than having `Least` drop every row, so this only returns None
+    /// on an empty matrix.  If several identical rows remain at the
+    /// end, that's the row returned.
+    pub fn filter(&self, which: Commonness, tie: TieBreak) -> Option<u64> {
+        let mut cands = self.clone();
+        for col in 0..self.width {
+            if cands.rows.len() <= 1 {
+                break;
+            }
+            let ones = cands.count_ones(col);
+            if ones == 0 || ones == cands.rows.len() {
+                continue;
+            }
+            let bit = cands.common_bit(col, which, tie);
+            let mask = self.column_bit(col);
+            cands.rows.retain(|r| (r & mask != 0) == bit);
+        }
+        cands.rows.first().copied()
+    }
+
+    /// Write a row as a binary number, with leading zeros.
+    pub fn format_row(&self, row: u64) -> String {
+        format!("{:0width$b}", row, width = self.width)
+    }
+}
+
+/// Parse one row per line, as binary numbers.
+impl FromStr for BitMatrix {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut lines = s.lines().filter(|l| !l.is_empty()).peekable();
+        let width = lines.peek().map_or(0, |l| l.len());
+        if !(1..=64).contains(&width) {
+            return Err(ParseError::BadWidth(width));
+        }
+
+        let mut ret = BitMatrix::new(width);
+        for (row, line) in lines.enumerate() {
+            if line.len() != width {
+                return Err(ParseError::Ragged {
+                    row,
+                    width: line.len(),
+                });
+            }
+            let mut value = 0;
+            for c in line.chars() {
+                value = (value << 1)
+                    | match c {
+                        '0' => 0,
+                        '1' => 1,
+                        _ => return Err(ParseError::NotABit(c)),
+                    };
+            }
+            ret.push(value);
+        }
+        Ok(ret)
+    }
+}
+
+#[test]
+fn test_bit_matrix() {
+    let m: BitMatrix =
+        "00100\n11110\n10110\n10111\n10101\n01111\n00111\n11100\n10000\n11001\n00010\n01010"
+            .parse()
+            .unwrap();
+    assert_eq!(m.column_ones(), vec![7, 5, 8, 7, 5]);
+    let gamma = m.common_bits(Commonness::Most, TieBreak::Zero);
+    let epsilon = m.common_bits(Commonness::Least, TieBreak::Zero);
+    assert_eq!((gamma, epsilon), (22, 9));
+    assert_eq!(m.filter(Commonness::Most, TieBreak::One), Some(23));
+    assert_eq!(m.filter(Commonness::Least, TieBreak::Zero), Some(10));
+    assert_eq!(m.format_row(10), "01010");
+}
+
+#[test]
+fn test_filter_duplicates() {
+    let m: BitMatrix = "10\n10".parse().unwrap();
+    assert_eq!(m.filter(Commonness::Least, TieBreak::Zero), Some(0b10));
+    assert_eq!(m.filter(Commonness::Most, TieBreak::One), Some(0b10));
+
+    // The first column is unanimous, the second one isn't.
+    let m: BitMatrix = "11\n11\n10".parse().unwrap();
+    assert_eq!(m.filter(Commonness::Least, TieBreak::Zero), Some(0b10));
+    assert_eq!(m.filter(Commonness::Most, TieBreak::One), Some(0b11));
+    assert_eq!(
+        BitMatrix::new(3).filter(Commonness::Most, TieBreak::One),
+        None
+    );
+}
+
+#[test]
+fn test_bit_matrix_64() {
+    let m: BitMatrix = [&"1".repeat(64), &"0".repeat(64), &"1".repeat(64)]
+        .map(|s| s.as_str())
+        .join("\n")
+        .parse()
+        .unwrap();
+    assert_eq!(m.common_bits(Commonness::Most, TieBreak::Zero), u64::MAX);
+    assert_eq!(m.common_bits(Commonness::Least, TieBreak::Zero), 0);
+}
+
+#[test]
+fn test_parse_errors() {
+    assert_eq!("".parse::<BitMatrix>(), Err(ParseError::BadWidth(0)));
+    assert_eq!(
+        "101\n10".parse::<BitMatrix>(),
+        Err(ParseError::Ragged { row: 1, width: 2 })
+    );
+    assert_eq!("1x1".parse::<BitMatrix>(), Err(ParseError::NotABit('x')));
+}
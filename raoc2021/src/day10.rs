@@ -1,93 +1,288 @@
 use lib::*;
 
-fn score_line_a(line: &str) -> u32 {
+/// A set of delimiter pairs, each an opening and a closing character.
+struct DelimiterSet {
+    pairs: Vec<(char, char)>,
+}
+
+impl DelimiterSet {
+    fn new(pairs: &[(char, char)]) -> DelimiterSet {
+        DelimiterSet {
+            pairs: pairs.to_vec(),
+        }
+    }
+
+    /// The four kinds of chunks of the navigation subsystem.
+    fn chunks() -> DelimiterSet {
+        DelimiterSet::new(&[('(', ')'), ('[', ']'), ('{', '}'), ('<', '>')])
+    }
+
+    /// The closing character for `open`, if it opens a chunk.
+    fn closer(&self, open: char) -> Option<char> {
+        self.pairs.iter().find(|p| p.0 == open).map(|p| p.1)
+    }
+
+    fn is_closer(&self, c: char) -> bool {
+        self.pairs.iter().any(|p| p.1 == c)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum SyntaxError {
+    /// The chunk opened last was closed with the wrong character.
+    /// Positions are in characters from the start of the line.
+    Corrupted {
+        position: usize,
+        expected: char,
+        found: char,
+    },
+    /// Some chunks were never closed.  Holds the characters that
+    /// would close them, in order.
+    Incomplete(String),
+    /// A chunk was closed while none were open.
+    UnbalancedClose { position: usize, found: char },
+    /// A character that's in none of the delimiter pairs.
+    NotADelimiter { position: usize, found: char },
+}
+
+/// Check that every chunk of `line` is closed, and closed right.
+/// Stops at the first error.
+fn check(line: &str, delimiters: &DelimiterSet) -> Result<(), SyntaxError> {
+    // The closers we expect, the innermost chunk last.
     let mut stack: Vec<char> = vec![];
-    for c in line.chars() {
-        match c {
-            '{' => stack.push(c),
-            '[' => stack.push(c),
-            '(' => stack.push(c),
-            '<' => stack.push(c),
-            '}' => {
-                if stack.pop().unwrap() != '{' {
-                    return 1197;
+    for (position, found) in line.chars().enumerate() {
+        if let Some(closer) = delimiters.closer(found) {
+            stack.push(closer);
+        } else if delimiters.is_closer(found) {
+            match stack.pop() {
+                None => return Err(SyntaxError::UnbalancedClose { position, found }),
+                Some(expected) if expected != found => {
+                    return Err(SyntaxError::Corrupted {
+                        position,
+                        expected,
+                        found,
+                    })
                 }
+                _ => (),
             }
-            ']' => {
-                if stack.pop().unwrap() != '[' {
-                    return 57;
-                }
-            }
-            '>' => {
-                if stack.pop().unwrap() != '<' {
-                    return 25137;
-                }
-            }
-            ')' => {
-                if stack.pop().unwrap() != '(' {
-                    return 3;
-                }
-            }
-            _ => panic!("Very bad input"),
+        } else {
+            return Err(SyntaxError::NotADelimiter { position, found });
         }
     }
-    0
+    if stack.is_empty() {
+        Ok(())
+    } else {
+        Err(SyntaxError::Incomplete(stack.into_iter().rev().collect()))
+    }
 }
 
-fn score_line_b(line: &str) -> u64 {
-    let mut stack: Vec<char> = vec![];
-    for c in line.chars() {
-        match c {
-            '{' => stack.push(c),
-            '[' => stack.push(c),
-            '(' => stack.push(c),
-            '<' => stack.push(c),
-            '}' => {
-                stack.pop();
-            }
-            ']' => {
-                stack.pop();
-            }
-            '>' => {
-                stack.pop();
-            }
-            ')' => {
-                stack.pop();
-            }
-            _ => panic!("Very bad input"),
+/// The smallest edit that makes a line valid: the first wrongly
+/// closed chunk gets the right closer, and whatever is still open is
+/// closed at the end.
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Repair {
+    original: String,
+    /// Where we replaced a character, and with what.
+    substitution: Option<(usize, char)>,
+    appended: String,
+}
+
+impl Repair {
+    fn is_noop(&self) -> bool {
+        self.substitution.is_none() && self.appended.is_empty()
+    }
+
+    fn fixed(&self) -> String {
+        let mut ret: String = self
+            .original
+            .chars()
+            .enumerate()
+            .map(|(i, c)| match self.substitution {
+                Some((position, new)) if position == i => new,
+                _ => c,
+            })
+            .collect();
+        ret.push_str(&self.appended);
+        ret
+    }
+}
+
+/// Show the repair as a diff: the line before and after, and a
+/// marker under each changed character.
+impl std::fmt::Display for Repair {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        writeln!(f, "- {}", self.original)?;
+        writeln!(f, "+ {}", self.fixed())?;
+        let mut marks: Vec<char> = vec![' '; self.original.chars().count()];
+        if let Some((position, _)) = self.substitution {
+            marks[position] = '^';
         }
+        marks.extend(self.appended.chars().map(|_| '+'));
+        write!(f, "  {}", marks.into_iter().collect::<String>().trim_end())
     }
-    let mut score: u64 = 0;
-    for c in stack.into_iter().rev() {
-        // print!("{}",c);
-        score *= 5;
-        score += match c {
-            '{' => 3,
-            '[' => 2,
-            '(' => 1,
-            '<' => 4,
-            _ => panic!("Very bad input"),
+}
+
+/// Find the [`Repair`] for `line`.  Lines that need more than that,
+/// like those with two wrongly closed chunks or too many closers,
+/// return the error that can't be fixed.
+fn repair(line: &str, delimiters: &DelimiterSet) -> Result<Repair, SyntaxError> {
+    let mut ret = Repair {
+        original: line.to_string(),
+        substitution: None,
+        appended: String::new(),
+    };
+    loop {
+        match check(&ret.fixed(), delimiters) {
+            Ok(()) => return Ok(ret),
+            Err(SyntaxError::Corrupted {
+                position, expected, ..
+            }) if ret.substitution.is_none() => ret.substitution = Some((position, expected)),
+            Err(SyntaxError::Incomplete(completion)) => ret.appended = completion,
+            Err(e) => return Err(e),
         }
     }
-    score
+}
+
+/// Points for the first illegal character of a corrupted line.
+const SYNTAX_POINTS: &[(char, u64)] = &[(')', 3), (']', 57), ('}', 1197), ('>', 25137)];
+
+/// Points for each character of a completion string.
+const COMPLETION_POINTS: &[(char, u64)] = &[(')', 1), (']', 2), ('}', 3), ('>', 4)];
+
+fn points(table: &[(char, u64)], c: char) -> u64 {
+    table
+        .iter()
+        .find(|p| p.0 == c)
+        .unwrap_or_else(|| panic!("No points for {}", c))
+        .1
+}
+
+fn completion_score(completion: &str, table: &[(char, u64)]) -> u64 {
+    completion
+        .chars()
+        .fold(0, |score, c| score * 5 + points(table, c))
 }
 
 fn main() {
-    let score = read_lines("../inputs/10.txt")
+    let chunks = DelimiterSet::chunks();
+    let results: Vec<Result<(), SyntaxError>> = read_lines("../inputs/10.txt")
         .unwrap()
-        .map(|line| score_line_a(&line.unwrap()))
-        .sum::<u32>();
+        .map(|line| check(&line.unwrap(), &chunks))
+        .collect();
+
+    let score: u64 = results
+        .iter()
+        .map(|r| match r {
+            Err(SyntaxError::Corrupted { found, .. }) => points(SYNTAX_POINTS, *found),
+            _ => 0,
+        })
+        .sum();
 
     println!("{}", score);
 
     // Part 2
 
-    let mut score: Vec<u64> = read_lines("../inputs/10.txt")
-        .unwrap()
-        .filter(|line| score_line_a(&line.as_ref().unwrap()) == 0)
-        .map(|line| score_line_b(&line.unwrap()))
+    let mut score: Vec<u64> = results
+        .iter()
+        .filter_map(|r| match r {
+            Err(SyntaxError::Incomplete(completion)) => {
+                Some(completion_score(completion, COMPLETION_POINTS))
+            }
+            _ => None,
+        })
         .collect();
 
     score.sort();
     println!("{:?}", score[score.len() / 2]);
+
+    // --repair shows how to fix every broken line.
+    if std::env::args().any(|a| a == "--repair") {
+        for line in read_lines("../inputs/10.txt").unwrap() {
+            match repair(&line.unwrap(), &chunks) {
+                Ok(r) if r.is_noop() => (),
+                Ok(r) => println!("{}\n", r),
+                Err(e) => println!("Can't repair: {:?}\n", e),
+            }
+        }
+    }
+}
+
+#[test]
+fn test_check() {
+    let chunks = DelimiterSet::chunks();
+    assert_eq!(check("[<>({}){}[([])<>]]", &chunks), Ok(()));
+    assert_eq!(
+        check("{([(<{}[<>[]}>{[]{[(<()>", &chunks),
+        Err(SyntaxError::Corrupted {
+            position: 12,
+            expected: ']',
+            found: '}'
+        })
+    );
+    let completion = "}}]])})]".to_string();
+    assert_eq!(
+        check("[({(<(())[]>[[{[]{<()<>>", &chunks),
+        Err(SyntaxError::Incomplete(completion.clone()))
+    );
+    assert_eq!(completion_score(&completion, COMPLETION_POINTS), 288957);
+    assert_eq!(
+        check("()]", &chunks),
+        Err(SyntaxError::UnbalancedClose {
+            position: 2,
+            found: ']'
+        })
+    );
+    assert_eq!(
+        check("(x)", &chunks),
+        Err(SyntaxError::NotADelimiter {
+            position: 1,
+            found: 'x'
+        })
+    );
+
+    // Other sets work just the same.
+    let quotes = DelimiterSet::new(&[('«', '»'), ('‹', '›')]);
+    assert_eq!(check("«‹›«»»", &quotes), Ok(()));
+    assert_eq!(
+        check("«‹»", &quotes),
+        Err(SyntaxError::Corrupted {
+            position: 2,
+            expected: '›',
+            found: '»'
+        })
+    );
+}
+
+#[test]
+fn test_repair() {
+    let chunks = DelimiterSet::chunks();
+    let r = repair("[({(<(())[]>[[{[]{<()<>>", &chunks).unwrap();
+    assert_eq!(r.substitution, None);
+    assert_eq!(r.appended, "}}]])})]");
+    assert_eq!(
+        r.to_string(),
+        "- [({(<(())[]>[[{[]{<()<>>\n+ [({(<(())[]>[[{[]{<()<>>}}]])})]\n                          ++++++++"
+    );
+
+    let r = repair("{()()()>", &chunks).unwrap();
+    assert_eq!(r.substitution, Some((7, '}')));
+    assert_eq!(r.to_string(), "- {()()()>\n+ {()()()}\n         ^");
+
+    // Corrupted lines are often incomplete too.
+    let r = repair("{([(<{}[<>[]}>{[]{[(<()>", &chunks).unwrap();
+    assert_eq!(r.substitution, Some((12, ']')));
+    assert_eq!(check(&r.fixed(), &chunks), Ok(()));
+
+    assert!(repair("<>[]", &chunks).unwrap().is_noop());
+    assert_eq!(
+        repair("(]>", &chunks),
+        Err(SyntaxError::UnbalancedClose {
+            position: 2,
+            found: '>'
+        })
+    );
+    assert!(matches!(
+        repair("(]<)", &chunks),
+        Err(SyntaxError::Corrupted { position: 3, .. })
+    ));
 }
@@ -1,50 +1,140 @@
+use ansi_term::Colour;
+use lib::render::FrameWriter;
+use lib::term::Painter;
 use lib::*;
+use std::collections::VecDeque;
 
-const MASK_FLASHED: u8 = 0b1000000;
-const MASK_DEFLASH: u8 = !MASK_FLASHED;
+/// What happened during a step.
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct StepReport {
+    flashes: usize,
+    /// The octopuses that flashed, in the order they did.
+    flashed_cells: Vec<(isize, isize)>,
+}
 
-/// Handle flashes.
-fn flash(map: &mut Vec2D<u8>) -> u32 {
-    let mut flashes = 0;
-    loop {
-        let mut again = false;
+/// A grid of octopuses, one step at a time.  Iterating over it runs
+/// steps forever.
+struct Simulation {
+    map: Vec2D<u8>,
+    steps: usize,
+}
+
+impl Simulation {
+    fn new(map: Vec2D<u8>) -> Simulation {
+        Simulation { map, steps: 0 }
+    }
+
+    /// Run a step.
+    ///
+    /// Octopuses go in a queue the moment their energy goes over 9,
+    /// which can only happen once per step, and flash when they get
+    /// out of it.  No need to look at the whole grid again after
+    /// each flash.
+    fn step(&mut self) -> StepReport {
+        let map = &mut self.map;
+        let mut queue = VecDeque::new();
         for x in 0..map.width() as isize {
             for y in 0..map.height() as isize {
-                if map[(x, y)] > 9 && (map[(x, y)] & MASK_FLASHED == 0) {
-                    flashes += 1;
-                    again = true;
-                    for x2 in [-1, 0, 1] {
-                        for y2 in [-1, 0, 1] {
-                            if map.test_coords(x + x2, y + y2) {
-                                map[(x + x2, y + y2)] += 1;
-                            }
+                map[(x, y)] += 1;
+                if map[(x, y)] == 10 {
+                    queue.push_back((x, y));
+                }
+            }
+        }
+
+        let mut flashed_cells = vec![];
+        while let Some((x, y)) = queue.pop_front() {
+            flashed_cells.push((x, y));
+            for x2 in [-1, 0, 1] {
+                for y2 in [-1, 0, 1] {
+                    let n = (x + x2, y + y2);
+                    if n != (x, y) && map.test_coords(n.0, n.1) {
+                        map[n] += 1;
+                        if map[n] == 10 {
+                            queue.push_back(n);
                         }
                     }
-                    map[(x, y)] = MASK_FLASHED
                 }
             }
         }
-        if !again {
-            break;
+
+        // "Finally, any octopus that flashed during this step has its energy level set to 0"
+        for &cell in &flashed_cells {
+            map[cell] = 0;
+        }
+        self.steps += 1;
+        StepReport {
+            flashes: flashed_cells.len(),
+            flashed_cells,
         }
     }
-    // "Finally, any octopus that flashed during this step has its energy level set to 0"
-    for o in &mut map.vec {
-        if *o > 10 {
-            *o = 0;
+
+    fn is_synchronised(&self, report: &StepReport) -> bool {
+        report.flashes == self.map.vec.len()
+    }
+
+    /// Run until every octopus flashes in the same step, and return
+    /// that step's number, counting from the very first step.
+    fn first_synchronised_step(&mut self) -> usize {
+        loop {
+            let report = self.step();
+            if self.is_synchronised(&report) {
+                return self.steps;
+            }
+        }
+    }
+
+    /// How many steps it takes, once synchronised, to synchronise
+    /// again.  Runs until the first synchronisation if needed.
+    ///
+    /// After a synchronised step all octopuses are at 0, so the whole
+    /// system repeats from there on: this is its period.
+    fn period(&mut self) -> usize {
+        if self.map.vec.iter().any(|v| *v != 0) {
+            self.first_synchronised_step();
         }
+        let start = self.steps;
+        self.first_synchronised_step() - start
     }
-    if flashes as usize == map.width() * map.height() {
-        println!("All flashed!");
+}
+
+impl Iterator for Simulation {
+    type Item = StepReport;
+
+    fn next(&mut self) -> Option<StepReport> {
+        Some(self.step())
     }
-    flashes
 }
 
-fn step(map: &mut Vec2D<u8>) -> u32 {
-    for value in &mut map.vec {
-        *value += 1;
+/// Just-flashed octopuses are white, others get brighter as their
+/// energy rises.
+fn energy_grey(v: &u8) -> u8 {
+    if *v == 0 {
+        255
+    } else {
+        *v * 20
     }
-    flash(map)
+}
+
+/// Draw the octopuses, making the ones that just flashed stand out
+/// if the painter allows it.
+fn draw(map: &Vec2D<u8>, painter: &Painter) {
+    map.draw_with(&|v| {
+        if *v == 0 {
+            painter.paint(Colour::Yellow.bold(), v)
+        } else {
+            painter.paint(Colour::Fixed(232 + *v * 2), v)
+        }
+    });
+}
+
+/// Read a grid of digits, one row per line.
+fn parse_grid<S: AsRef<str>>(lines: &[S]) -> Vec2D<u8> {
+    let rows: Vec<Vec<u8>> = lines
+        .iter()
+        .map(|l| l.as_ref().chars().map(read_digit).collect())
+        .collect();
+    Vec2D::from_rows(&rows)
 }
 
 fn main() {
@@ -53,33 +143,54 @@ fn main() {
         .map(|l| l.unwrap())
         .collect();
 
-    let height = raw.len();
-    let width = raw[1].len();
+    let map = parse_grid(&raw);
 
-    let mut map: Vec2D<u8> = Vec2D::new(width, height, 0);
+    let mut frames = FrameWriter::from_args("day11");
+    let painter = Painter::from_args();
 
-    for y in 0..height {
-        for x in 0..width {
-            map[(x as isize, y as isize)] = read_digit(raw[y].chars().nth(x).unwrap());
+    let mut sim = Simulation::new(map.clone());
+    let mut flashes = 0;
+    for _ in 0..100 {
+        flashes += sim.step().flashes;
+        if let Some(frames) = &mut frames {
+            frames.pgm(&sim.map, &energy_grey).unwrap();
         }
     }
+    draw(&sim.map, &painter);
+    println!("Part 1: there were {} flashes", flashes);
 
-    let mut map_b = map.clone();
+    let mut sim = Simulation::new(map);
+    let first = sim.first_synchronised_step();
+    draw(&sim.map, &painter);
+    println!("All flashed at step {}", first);
+    println!("Then again every {} steps", sim.period());
+}
 
-    let mut flashes = 0;
-    let total = (width * height) as u32;
+#[test]
+fn test_simulation() {
+    let example = [
+        "5483143223",
+        "2745854711",
+        "5264556173",
+        "6141336146",
+        "6357385478",
+        "4167524645",
+        "2176841721",
+        "6882881134",
+        "4846848554",
+        "5283751526",
+    ];
+    let map = parse_grid(&example);
 
-    for _ in 0..100 {
-        flashes += step(&mut map);
-    }
-    println!("Part 1: there were {} flashes", flashes);
+    let mut sim = Simulation::new(map.clone());
+    assert_eq!(sim.next().unwrap().flashes, 0);
+    let report = sim.next().unwrap();
+    assert_eq!(report.flashes, 35);
+    assert_eq!(report.flashed_cells.len(), 35);
+    assert_eq!(sim.take(8).map(|r| r.flashes).sum::<usize>(), 204 - 35);
 
-    let mut i = 0;
-    loop {
-        i += 1;
-        if total == step(&mut map_b) {
-            println!("All flashed at step {}", i);
-            break;
-        }
-    }
+    let mut sim = Simulation::new(map);
+    assert_eq!(sim.first_synchronised_step(), 195);
+    assert_eq!(sim.period(), 10);
+    assert_eq!(sim.steps, 205);
 }
@@ -7,20 +7,156 @@ struct Cave {
     large: bool,
 }
 
+/// How many times we may visit small caves.
+#[derive(PartialEq, Eq, Copy, Clone, Debug)]
+enum SmallVisits {
+    /// At most once.
+    Once,
+    /// A single small cave twice, the other ones at most once.
+    OneTwice,
+    /// Any small cave up to k times.
+    UpTo(u8),
+}
+
+/// The rules of a walk through the caves.
+#[derive(PartialEq, Eq, Clone, Debug)]
+struct VisitPolicy {
+    small: SmallVisits,
+    /// Caves, large or small, that can never be revisited.
+    never_revisit: HashSet<String>,
+}
+
+impl VisitPolicy {
+    /// A policy with the given rule for small caves, where the
+    /// `start` cave can't be revisited.
+    pub fn new(small: SmallVisits) -> VisitPolicy {
+        VisitPolicy {
+            small,
+            never_revisit: HashSet::from([String::from("start")]),
+        }
+    }
+
+    /// Forbid revisiting the cave called `name`.
+    pub fn never_revisit(mut self, name: &str) -> VisitPolicy {
+        self.never_revisit.insert(name.to_string());
+        self
+    }
+}
+
+/// A VisitPolicy, resolved against the caves of a CaveSystem.
+struct Limits {
+    /// The maximum number of visits of each cave, by index.
+    max: Vec<Option<u8>>,
+    /// Whether each cave, by index, can get an extra visit under
+    /// SmallVisits::OneTwice.
+    extra: Vec<bool>,
+    /// Where each limited cave's count starts in Counts::Packed, or
+    /// None if they don't all fit and we use Counts::Wide.
+    offset: Option<Vec<u32>>,
+    spare: bool,
+}
+
+/// Visit counts of the limited caves.
+#[derive(PartialEq, Eq, Clone, Debug, Hash)]
+enum Counts {
+    /// Packed together, each in as few bits as its maximum needs.
+    /// That's a single bit for caves we may visit once, so `Once` and
+    /// `OneTwice` get a plain bitmask, and unlimited caves take no
+    /// room at all.
+    Packed(u128),
+    /// One byte per cave, by index, for when that takes more than
+    /// 128 bits.
+    Wide(Vec<u8>),
+}
+
+/// How many times we've been to each limited cave, and whether we
+/// still have an extra visit to spend.  This is our memoization key,
+/// so it's kept as small as the caves allow.
+#[derive(PartialEq, Eq, Clone, Debug, Hash)]
+struct Visits {
+    counts: Counts,
+    spare: bool,
+}
+
+/// The number of bits needed to count up to `max`.
+fn width(max: u8) -> u32 {
+    u8::BITS - max.leading_zeros()
+}
+
+impl Limits {
+    fn new(max: Vec<Option<u8>>, extra: Vec<bool>, spare: bool) -> Limits {
+        let mut offset = vec![0; max.len()];
+        let mut bits = 0;
+        for (idx, m) in max.iter().enumerate() {
+            if let Some(m) = m {
+                offset[idx] = bits;
+                bits += width(*m);
+            }
+        }
+        Limits {
+            max,
+            extra,
+            offset: (bits <= u128::BITS).then_some(offset),
+            spare,
+        }
+    }
+
+    fn start(&self) -> Visits {
+        Visits {
+            counts: match self.offset {
+                Some(_) => Counts::Packed(0),
+                None => Counts::Wide(vec![0; self.max.len()]),
+            },
+            spare: self.spare,
+        }
+    }
+
+    /// The visit counts after entering `cave`, if we're allowed to.
+    fn enter(&self, visits: &Visits, cave: &Cave) -> Option<Visits> {
+        let mut ret = visits.clone();
+        let max = match self.max[cave.idx] {
+            None => return Some(ret),
+            Some(max) => max,
+        };
+        let count = match &visits.counts {
+            Counts::Packed(counts) => {
+                let offset = self.offset.as_ref().unwrap()[cave.idx];
+                ((counts >> offset) & ((1 << width(max)) - 1)) as u8
+            }
+            Counts::Wide(counts) => counts[cave.idx],
+        };
+        if count == max {
+            if !(visits.spare && self.extra[cave.idx]) {
+                return None;
+            }
+            // The extra visit isn't counted: we'll never need to know.
+            ret.spare = false;
+        } else {
+            match &mut ret.counts {
+                Counts::Packed(counts) => *counts += 1 << self.offset.as_ref().unwrap()[cave.idx],
+                Counts::Wide(counts) => counts[cave.idx] += 1,
+            }
+        }
+        Some(ret)
+    }
+}
+
 struct CaveSystem {
-    next_idx: usize,
     names_for: HashMap<String, Cave>,
     names_rev: HashMap<Cave, String>,
-    edges: Vec<(Cave, Cave)>,
+    /// Caves, by index.
+    caves: Vec<Cave>,
+    /// The neighbors of each cave, by index.
+    adjacency: Vec<Vec<Cave>>,
 }
 
 impl CaveSystem {
     pub fn new() -> CaveSystem {
         CaveSystem {
-            next_idx: 0,
             names_for: HashMap::new(),
             names_rev: HashMap::new(),
-            edges: vec![],
+            caves: vec![],
+            adjacency: vec![],
         }
     }
 
@@ -30,18 +166,13 @@ impl CaveSystem {
             *idx
         } else {
             let new = Cave {
-                idx: self.next_idx,
-                large: name.chars().nth(0).unwrap().is_uppercase(),
+                idx: self.caves.len(),
+                large: name.chars().next().unwrap().is_uppercase(),
             };
             self.names_for.insert(name.clone(), new);
-            self.names_rev.insert(new, name.clone());
-            self.next_idx += 1;
-            println!(
-                "Learned about a new {} cave called \"{}\" at id {}.",
-                if new.large { "large" } else { "small" },
-                &name,
-                new.idx
-            );
+            self.names_rev.insert(new, name);
+            self.caves.push(new);
+            self.adjacency.push(vec![]);
             new
         }
     }
@@ -49,50 +180,90 @@ impl CaveSystem {
     pub fn connect(&mut self, a: &str, b: &str) {
         let a = self.insert(a);
         let b = self.insert(b);
-        self.edges.push((a, b));
-        self.edges.push((b, a));
+        self.adjacency[a.idx].push(b);
+        self.adjacency[b.idx].push(a);
     }
 
-    pub fn next_caves(&self, from: &Cave) -> Vec<Cave> {
-        self.edges
-            .iter()
-            .filter(|p| &p.0 == from)
-            .map(|p| p.1)
-            .collect()
+    pub fn next_caves(&self, from: &Cave) -> &[Cave] {
+        &self.adjacency[from.idx]
+    }
+
+    /// The cave called `name`, if there's one.
+    pub fn cave(&self, name: &str) -> Option<Cave> {
+        self.names_for.get(name).copied()
     }
 
-    fn do_walk(
+    fn limits(&self, policy: &VisitPolicy) -> Limits {
+        let mut max = vec![None; self.caves.len()];
+        let mut extra = vec![false; self.caves.len()];
+        for (name, cave) in &self.names_for {
+            if policy.never_revisit.contains(name) {
+                max[cave.idx] = Some(1);
+            } else if !cave.large {
+                max[cave.idx] = Some(match policy.small {
+                    SmallVisits::Once | SmallVisits::OneTwice => 1,
+                    SmallVisits::UpTo(k) => k,
+                });
+                extra[cave.idx] = true;
+            }
+        }
+        Limits::new(max, extra, policy.small == SmallVisits::OneTwice)
+    }
+
+    /// Count the paths from `start` to `goal`.
+    ///
+    /// Where we can go next only depends on where we are and on how
+    /// many times we've been to each limited cave, so we memoize on
+    /// that.
+    pub fn count_paths(&self, start: &Cave, goal: &Cave, policy: &VisitPolicy) -> u64 {
+        let limits = self.limits(policy);
+        let mut memo = HashMap::new();
+        match limits.enter(&limits.start(), start) {
+            Some(visits) => self.do_count(start, goal, visits, &limits, &mut memo),
+            None => 0,
+        }
+    }
+
+    fn do_count(
         &self,
-        start: &Cave,
+        cave: &Cave,
         goal: &Cave,
-        revisit_small: bool,
-        mut way: Vec<Cave>,
-        ways: &mut Vec<Vec<Cave>>,
-    ) {
-        way.push(*start);
-
-        if start == goal {
-            ways.push(way);
-            return;
-        }
-
-        let no_go: Vec<&Cave> = way.iter().filter(|c| !c.large).collect();
-        for next in self.next_caves(start) {
-            if !no_go.contains(&&next) {
-                self.do_walk(&next, goal, revisit_small, way.clone(), ways)
-            } else if revisit_small && next.idx > 1 {
-                // @FIXME This......^^^^^^^^^^^^^^^ is a trick:
-                // because we insert start and end manually before we
-                // parse the input, they get the IDs 0 and 1.
-                self.do_walk(&next, goal, false, way.clone(), ways)
+        visits: Visits,
+        limits: &Limits,
+        memo: &mut HashMap<(usize, Visits), u64>,
+    ) -> u64 {
+        if cave == goal {
+            return 1;
+        }
+        let key = (cave.idx, visits);
+        if let Some(count) = memo.get(&key) {
+            return *count;
+        }
+
+        let mut count = 0;
+        for next in self.next_caves(cave) {
+            if let Some(visits) = limits.enter(&key.1, next) {
+                count += self.do_count(next, goal, visits, limits, memo);
             }
         }
+        memo.insert(key, count);
+        count
     }
 
-    pub fn walk(&self, start: &Cave, goal: &Cave, revisit_small: bool) -> Vec<Vec<Cave>> {
-        let mut ret: Vec<Vec<Cave>> = vec![];
-        self.do_walk(start, goal, revisit_small, vec![], &mut ret);
-        ret
+    /// Enumerate the paths from `start` to `goal`, lazily.
+    pub fn paths(&self, start: &Cave, goal: &Cave, policy: &VisitPolicy) -> Paths<'_> {
+        let limits = self.limits(policy);
+        let (way, stack) = match limits.enter(&limits.start(), start) {
+            Some(visits) => (vec![*start], vec![(0, visits)]),
+            None => (vec![], vec![]),
+        };
+        Paths {
+            caves: self,
+            goal: *goal,
+            limits,
+            way,
+            stack,
+        }
     }
 
     pub fn cave_name(&self, cave: &Cave) -> Option<&String> {
@@ -100,50 +271,276 @@ impl CaveSystem {
     }
 
     pub fn print_edges(&self) {
-        for edge in &self.edges {
+        for (a, b) in self.edges() {
             println!(
                 "{} <-> {}",
-                self.cave_name(&edge.0).unwrap(),
-                self.cave_name(&edge.1).unwrap(),
+                self.cave_name(&a).unwrap(),
+                self.cave_name(&b).unwrap(),
             )
         }
     }
+
+    /// Each edge, once.
+    fn edges(&self) -> impl Iterator<Item = (Cave, Cave)> + '_ {
+        self.caves.iter().flat_map(move |a| {
+            self.next_caves(a)
+                .iter()
+                .filter(move |b| b.idx >= a.idx)
+                .map(move |b| (*a, *b))
+        })
+    }
+
+    /// Export the system in Graphviz's DOT format.  Large caves are
+    /// filled boxes, small ones are ellipses.
+    pub fn to_dot(&self) -> String {
+        let mut ret = String::from("graph caves {\n");
+        for cave in &self.caves {
+            let shape = if cave.large {
+                "shape=box, style=filled, fillcolor=lightblue"
+            } else {
+                "shape=ellipse"
+            };
+            ret.push_str(&format!(
+                "    \"{}\" [{}];\n",
+                self.cave_name(cave).unwrap(),
+                shape
+            ));
+        }
+        for (a, b) in self.edges() {
+            ret.push_str(&format!(
+                "    \"{}\" -- \"{}\";\n",
+                self.cave_name(&a).unwrap(),
+                self.cave_name(&b).unwrap()
+            ));
+        }
+        ret.push_str("}\n");
+        ret
+    }
+}
+
+/// A depth-first iterator over the paths of a CaveSystem.
+struct Paths<'a> {
+    caves: &'a CaveSystem,
+    goal: Cave,
+    limits: Limits,
+    /// The path we're exploring.
+    way: Vec<Cave>,
+    /// For each cave on `way`, the index of the next neighbor to
+    /// try, and the visit counts once we got there.
+    stack: Vec<(usize, Visits)>,
+}
+
+impl<'a> Iterator for Paths<'a> {
+    type Item = Vec<Cave>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some(cave) = self.way.last() {
+            let (i, visits) = self.stack.last_mut().unwrap();
+            let nexts = self.caves.next_caves(cave);
+            if *cave == self.goal || *i >= nexts.len() {
+                self.way.pop();
+                self.stack.pop();
+                continue;
+            }
+            *i += 1;
+
+            let next = nexts[*i - 1];
+            if let Some(visits) = self.limits.enter(visits, &next) {
+                self.way.push(next);
+                self.stack.push((0, visits));
+                if next == self.goal {
+                    return Some(self.way.clone());
+                }
+            }
+        }
+        None
+    }
 }
 
 fn main() {
     let mut cs = CaveSystem::new();
-    let start = cs.insert("start");
-    let end = cs.insert("end");
 
     for line in read_lines("../inputs/12.txt").unwrap() {
         let line = line.unwrap();
-        let mut sides = line.split("-");
+        let mut sides = line.split('-');
         let a = &sides.next().unwrap();
         let b = &sides.next().unwrap();
         cs.connect(a, b);
     }
 
+    if std::env::args().any(|a| a == "--dot") {
+        print!("{}", cs.to_dot());
+        return;
+    }
     cs.print_edges();
 
-    let paths = cs.walk(&start, &end, false);
-    let mut counta = 0;
-    for path in &paths {
-        counta += 1;
-        for cave in path.into_iter() {
-            print!("{} ", cs.cave_name(&cave).unwrap())
+    let start = cs.cave("start").expect("No start cave");
+    let end = cs.cave("end").expect("No end cave");
+
+    // --never-revisit NAME forbids revisiting NAME, --up-to K also
+    // counts paths visiting small caves up to K times.
+    let args: Vec<String> = std::env::args().collect();
+    let print_paths = args.iter().any(|a| a == "--paths");
+    let mut policies = vec![
+        ("part 1", VisitPolicy::new(SmallVisits::Once)),
+        ("part 2", VisitPolicy::new(SmallVisits::OneTwice)),
+    ];
+    for pair in args.windows(2) {
+        if pair[0] == "--up-to" {
+            let k = pair[1].parse().expect("--up-to wants a number");
+            policies.push(("up to k", VisitPolicy::new(SmallVisits::UpTo(k))));
+        }
+    }
+    for pair in args.windows(2) {
+        if pair[0] == "--never-revisit" {
+            for (_, policy) in &mut policies {
+                *policy = policy.clone().never_revisit(&pair[1]);
+            }
+        }
+    }
+
+    for (part, policy) in policies {
+        if print_paths {
+            for path in cs.paths(&start, &end, &policy) {
+                for cave in &path {
+                    print!("{} ", cs.cave_name(cave).unwrap())
+                }
+                println!();
+            }
         }
-        println!();
+        println!(
+            "{} paths found ({}).",
+            cs.count_paths(&start, &end, &policy),
+            part
+        );
     }
+}
 
-    let paths = cs.walk(&start, &end, true);
-    let mut countb = 0;
-    for path in &paths {
-        countb += 1;
-        for cave in path.into_iter() {
-            print!("{} ", cs.cave_name(&cave).unwrap())
+#[cfg(test)]
+fn example() -> CaveSystem {
+    let mut cs = CaveSystem::new();
+    for line in ["start-A", "start-b", "A-c", "A-b", "b-d", "A-end", "b-end"] {
+        let (a, b) = line.split_once('-').unwrap();
+        cs.connect(a, b);
+    }
+    cs
+}
+
+#[test]
+fn test_count_paths() {
+    let cs = example();
+    let start = cs.cave("start").unwrap();
+    let end = cs.cave("end").unwrap();
+    for (small, count) in [(SmallVisits::Once, 10), (SmallVisits::OneTwice, 36)] {
+        let policy = VisitPolicy::new(small);
+        assert_eq!(cs.count_paths(&start, &end, &policy), count);
+        assert_eq!(cs.paths(&start, &end, &policy).count() as u64, count);
+    }
+}
+
+/// Count paths the slow way, straight from the rules, to check the
+/// others against.
+#[cfg(test)]
+fn brute_count(cs: &CaveSystem, way: &mut Vec<String>, policy: &VisitPolicy) -> u64 {
+    let here = way.last().unwrap().clone();
+    if here == "end" {
+        return 1;
+    }
+    let mut ret = 0;
+    for next in cs.next_caves(&cs.cave(&here).unwrap()) {
+        let name = cs.cave_name(next).unwrap().clone();
+        let small = |n: &String| n.chars().next().unwrap().is_lowercase();
+        way.push(name);
+        let times = |n: &String| way.iter().filter(|w| *w == n).count();
+        let ok = way
+            .iter()
+            .all(|n| !policy.never_revisit.contains(n) || times(n) == 1)
+            && match policy.small {
+                SmallVisits::Once => way.iter().filter(|n| small(n)).all(|n| times(n) == 1),
+                SmallVisits::OneTwice => {
+                    let twice: HashSet<&String> =
+                        way.iter().filter(|n| small(n) && times(n) > 1).collect();
+                    way.iter().filter(|n| small(n)).all(|n| times(n) <= 2) && twice.len() <= 1
+                }
+                SmallVisits::UpTo(k) => way
+                    .iter()
+                    .filter(|n| small(n))
+                    .all(|n| times(n) <= k as usize),
+            };
+        if ok {
+            ret += brute_count(cs, way, policy);
         }
-        println!();
+        way.pop();
+    }
+    ret
+}
+
+#[test]
+fn test_visit_policies() {
+    let cs = example();
+    let start = cs.cave("start").unwrap();
+    let end = cs.cave("end").unwrap();
+    let count = |policy: &VisitPolicy| {
+        let ret = cs.count_paths(&start, &end, policy);
+        assert_eq!(cs.paths(&start, &end, policy).count() as u64, ret);
+        assert_eq!(
+            brute_count(&cs, &mut vec!["start".to_string()], policy),
+            ret
+        );
+        ret
+    };
+    assert_eq!(count(&VisitPolicy::new(SmallVisits::Once)), 10);
+    assert_eq!(count(&VisitPolicy::new(SmallVisits::OneTwice)), 36);
+    assert_eq!(count(&VisitPolicy::new(SmallVisits::UpTo(1))), 10);
+    assert_eq!(count(&VisitPolicy::new(SmallVisits::UpTo(2))), 54);
+    assert_eq!(count(&VisitPolicy::new(SmallVisits::UpTo(3))), 278);
+    // start,A,end  start,A,b,end  start,b,A,end  start,b,end
+    let no_a = VisitPolicy::new(SmallVisits::Once).never_revisit("A");
+    assert_eq!(count(&no_a), 4);
+    let no_b = VisitPolicy::new(SmallVisits::OneTwice).never_revisit("b");
+    assert_eq!(count(&no_b), 16);
+    let no_a_b = VisitPolicy::new(SmallVisits::UpTo(2))
+        .never_revisit("A")
+        .never_revisit("b");
+    assert_eq!(count(&no_a_b), 4);
+}
+
+#[test]
+fn test_many_caves() {
+    // More small caves than fit in a packed key, all between start
+    // and end, and two of them next to each other.
+    let mut cs = CaveSystem::new();
+    for i in 0..200 {
+        cs.connect("start", &format!("c{}", i));
+        cs.connect(&format!("c{}", i), "end");
     }
-    println!("{} paths found (part 1).", counta);
-    println!("{} paths found (part 2).", countb);
+    cs.connect("c0", "c1");
+    let start = cs.cave("start").unwrap();
+    let end = cs.cave("end").unwrap();
+    let count = |policy: &VisitPolicy| {
+        assert!(cs.limits(policy).offset.is_none());
+        let ret = cs.count_paths(&start, &end, policy);
+        assert_eq!(cs.paths(&start, &end, policy).count() as u64, ret);
+        ret
+    };
+    assert_eq!(count(&VisitPolicy::new(SmallVisits::Once)), 202);
+    // Also c0,c1,c0 and c1,c0,c1.
+    assert_eq!(count(&VisitPolicy::new(SmallVisits::OneTwice)), 204);
+    // Also c0,c1,c0,c1 and c1,c0,c1,c0.
+    assert_eq!(count(&VisitPolicy::new(SmallVisits::UpTo(2))), 206);
+
+    // Small systems still get packed keys.
+    assert!(example()
+        .limits(&VisitPolicy::new(SmallVisits::UpTo(3)))
+        .offset
+        .is_some());
+}
+
+#[test]
+fn test_to_dot() {
+    let dot = example().to_dot();
+    assert!(dot.starts_with("graph caves {\n"));
+    assert!(dot.contains("\"A\" [shape=box, style=filled, fillcolor=lightblue];"));
+    assert!(dot.contains("\"b\" [shape=ellipse];"));
+    assert_eq!(dot.matches(" -- ").count(), 7);
 }
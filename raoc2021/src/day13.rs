@@ -1,5 +1,6 @@
+use lib::render::FrameWriter;
 use lib::*;
-use std::cmp::{max, min};
+use std::collections::HashSet;
 use std::str::FromStr;
 
 #[derive(Clone, Copy, Debug, Eq, PartialEq)]
@@ -11,7 +12,94 @@ enum FoldAxis {
 #[derive(Clone, Copy, Debug, Eq, PartialEq)]
 struct Fold {
     axis: FoldAxis,
-    i: isize,
+    i: i32,
+}
+
+impl Fold {
+    /// Where the dot at (x, y) ends up after this fold.  Dots past
+    /// the fold line are mirrored over it, which works wherever the
+    /// line is: if it's not in the middle, the longer half just
+    /// sticks out (possibly at negative coordinates).
+    fn apply(&self, (x, y): (i32, i32)) -> (i32, i32) {
+        match self.axis {
+            FoldAxis::X if x > self.i => (2 * self.i - x, y),
+            FoldAxis::Y if y > self.i => (x, 2 * self.i - y),
+            _ => (x, y),
+        }
+    }
+}
+
+/// Parse a fold description of the form
+/// fold along x=655
+impl FromStr for Fold {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (axis, i) = s
+            .strip_prefix("fold along ")
+            .and_then(|s| s.split_once('='))
+            .ok_or(())?;
+        let axis = match axis {
+            "x" => FoldAxis::X,
+            "y" => FoldAxis::Y,
+            _ => return Err(()),
+        };
+        let i = i.parse::<i32>().map_err(|_| ())?;
+        Ok(Fold { axis, i })
+    }
+}
+
+/// A sheet of transparent paper, as the set of its dots.
+#[derive(Clone, Debug, Default)]
+struct Paper {
+    dots: HashSet<(i32, i32)>,
+}
+
+impl Paper {
+    fn fold(&mut self, fold: &Fold) {
+        self.dots = self.dots.iter().map(|dot| fold.apply(*dot)).collect();
+    }
+
+    /// The number of visible dots.  Overlapping dots count as one.
+    fn count(&self) -> usize {
+        self.dots.len()
+    }
+
+    /// The top-left and bottom-right corners (inclusive) of the
+    /// smallest rectangle containing all dots.
+    fn bounding_box(&self) -> Option<((i32, i32), (i32, i32))> {
+        let min_x = self.dots.iter().map(|d| d.0).min()?;
+        let min_y = self.dots.iter().map(|d| d.1).min()?;
+        let max_x = self.dots.iter().map(|d| d.0).max()?;
+        let max_y = self.dots.iter().map(|d| d.1).max()?;
+        Some(((min_x, min_y), (max_x, max_y)))
+    }
+
+    /// Rasterize the paper.  The origin is kept in the image, so
+    /// that text doesn't lose its leading blank columns.
+    fn to_vec2d(&self) -> Vec2D<bool> {
+        let ((min_x, min_y), (max_x, max_y)) = self.bounding_box().unwrap_or(((0, 0), (0, 0)));
+        self.to_vec2d_in(((min_x.min(0), min_y.min(0)), (max_x, max_y)))
+    }
+
+    /// Rasterize the part of the paper within `corners`, top-left
+    /// and bottom-right inclusive.
+    fn to_vec2d_in(
+        &self,
+        ((min_x, min_y), (max_x, max_y)): ((i32, i32), (i32, i32)),
+    ) -> Vec2D<bool> {
+        let mut ret = Vec2D::new(
+            (max_x - min_x + 1) as usize,
+            (max_y - min_y + 1) as usize,
+            false,
+        );
+        for (x, y) in &self.dots {
+            if (min_x..=max_x).contains(x) && (min_y..=max_y).contains(y) {
+                ret[((x - min_x) as isize, (y - min_y) as isize)] = true;
+            }
+        }
+        ret
+    }
 }
 
 fn cut<T: FromStr>(s: &str, delim: &str) -> Option<(T, T)> {
@@ -26,89 +114,74 @@ fn print_bool(b: &bool) -> String {
     if *b { "#" } else { "." }.to_string()
 }
 
-fn fold(paper: &mut Vec2D<bool>, fold: &Fold) {
-    let i = fold.i;
-    if fold.axis == FoldAxis::X {
-        // Horizontal
-        let count: isize = min(i + 1, paper.width() as isize - i);
-        for d in 1..count {
-            for y in 0..paper.height() as isize {
-                // println!("i={}, d={}, y={}, count={}", i, d, y, count);
-                paper[(i - d, y)] |= paper[(i + d, y)];
-                paper[(i + d, y)] = false;
-            }
-        }
-    } else if fold.axis == FoldAxis::Y {
-        // Vertical
-        let count: isize = min(i + 1, paper.height() as isize - i);
-        for d in 1..count {
-            for x in 0..paper.width() as isize {
-                paper[(x, i - d)] |= paper[(x, i + d)];
-                paper[(x, i + d)] = false;
-            }
-        }
-    }
-}
-
 fn main() {
-    // First iteration to get dimensions
-    let mut width: usize = 0;
-    let mut height: usize = 0;
-    for line in read_lines("../inputs/13.txt").unwrap() {
-        let line = line.unwrap();
-
-        if line.is_empty() {
-            break;
-        }
-
-        let (x, y) = cut::<usize>(&line, ",").unwrap();
-        width = max(x + 1, width);
-        height = max(y + 1, height);
-    }
-
-    println!("Init for wh {}×{}", width, height);
-    let mut paper: Vec2D<bool> = Vec2D::new(width, height, false);
+    let mut paper = Paper::default();
     let mut folds: Vec<Fold> = vec![];
 
-    let mut step1 = true;
     for line in read_lines("../inputs/13.txt").unwrap() {
         let line = line.unwrap();
 
         if line.is_empty() {
-            step1 = false;
             continue;
         }
-        if step1 {
-            // Read dots
-            if let Some((x, y)) = cut::<isize>(&line, ",") {
-                paper[(x, y)] = true;
-            } else {
-                panic!("no parse.");
-            }
+        if let Some(dot) = cut::<i32>(&line, ",") {
+            paper.dots.insert(dot);
+        } else if let Ok(fold) = line.parse::<Fold>() {
+            folds.push(fold);
         } else {
-            // Read folds
-            let axis = if line.chars().nth(11).unwrap() == 'x' {
-                FoldAxis::X
-            } else {
-                FoldAxis::Y
-            };
-            let i = line.rsplit("=").next().unwrap().parse::<isize>().unwrap();
-            folds.push(Fold { axis, i })
+            panic!("no parse: {}", line);
         }
     }
-    fold(&mut paper, &folds[0]);
-    println!("Part 1: {}", &paper.vec.iter().filter(|b| **b).count());
-    for fld in folds.iter().skip(1) {
-        println!("Folding {:?}", &fld);
-        fold(&mut paper, &fld);
+
+    // Keep every stage, to draw them all on the same canvas.
+    let mut stages = vec![paper.clone()];
+    for (n, fold) in folds.iter().enumerate() {
+        paper.fold(fold);
+        stages.push(paper.clone());
+        println!("After {:?}: {} visible dots", fold, paper.count());
+        if n == 0 {
+            println!("Part 1: {}", paper.count());
+        }
     }
 
-    paper.draw_with(&print_bool);
-    // This is VERY hacky
-    for x in 0..200 {
-        for y in 0..200 {
-            print!("{}", if paper[(x, y)] { "▉" } else { " " })
+    if let Some(frames) = &mut FrameWriter::from_args("day13") {
+        let boxes: Vec<_> = stages.iter().filter_map(Paper::bounding_box).collect();
+        let canvas = (
+            (
+                boxes.iter().map(|b| b.0 .0).min().unwrap_or(0),
+                boxes.iter().map(|b| b.0 .1).min().unwrap_or(0),
+            ),
+            (
+                boxes.iter().map(|b| b.1 .0).max().unwrap_or(0),
+                boxes.iter().map(|b| b.1 .1).max().unwrap_or(0),
+            ),
+        );
+        for stage in &stages {
+            frames
+                .pgm(&stage.to_vec2d_in(canvas), &|b| if *b { 0 } else { 255 })
+                .unwrap();
         }
-        println!();
     }
+
+    if let Some((top_left, bottom_right)) = paper.bounding_box() {
+        println!("Bounding box: {:?} to {:?}", top_left, bottom_right);
+    }
+    let sheet = paper.to_vec2d();
+    sheet.draw_with(&print_bool);
+    match ocr::read(&sheet) {
+        Ok(code) => println!("Part 2: {}", code),
+        Err(err) => println!("Part 2: {}", err),
+    }
+}
+
+#[test]
+fn test_fold_off_center() {
+    let mut paper = Paper::default();
+    paper.dots.extend([(0, 0), (1, 0), (5, 0), (6, 1)]);
+    paper.fold(&"fold along x=2".parse().unwrap());
+    assert_eq!(
+        paper.dots,
+        HashSet::from([(0, 0), (1, 0), (-1, 0), (-2, 1)])
+    );
+    assert_eq!(paper.bounding_box(), Some(((-2, 0), (1, 1))));
 }
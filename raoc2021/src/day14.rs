@@ -14,109 +14,179 @@
 //
 // and so on.
 //
-// To get the final count, we simply break those pairs and add their
-// value to the total sum of each element.  Eg, CB=68 means we add 68
-// to the total C count and to the total B count.
+// To get the final count, we count the left element of each pair:
+// each element of the polymer is the left side of exactly one pair,
+// except the last one, which we add separately.  Insertions never
+// change the last element.
 //
-// Because each element *but the first and the last of the original
-// polymer* will appear twice, we add one of those to the total count,
-// then divide each value by 2 to get the actual count.  The rest is
-// trivial.
+// Since evolution is linear in the pair counts, a step is a matrix
+// product, and n steps is a matrix power, which we can compute in
+// log(n) multiplications.  Counts grow exponentially, so for large
+// n we compute them modulo some number.
 
+use lib::matrix::{self, Matrix};
 use lib::*;
-use std::collections::HashMap;
+use std::collections::{BTreeSet, HashMap};
 
 type Pair = (u8, u8);
 /// A state is simply a count of pairs (we don't preserve structure)
-type State = HashMap<Pair, u64>;
+type State = HashMap<Pair, u128>;
 type Rules = HashMap<Pair, u8>;
-type Count = HashMap<u8, u64>;
-
-fn compute() -> u64 {
-    let (raw_state, rules) = read_input();
-    // We need to preserve the first and the last component of the
-    // polymer.  Since we're later going to break the polymer into a
-    // simple list of pairs, losing structure, each element *but the
-    // first and last* will be counted twice.  We need to restore
-    // these two befor the final count.
-    let first = raw_state.first().unwrap();
-    let last = raw_state.last().unwrap();
-
-    // We break the initial state into pairs.
-    let mut state: State = State::new();
-    for i in 0..raw_state.len() - 1 {
-        let key = (raw_state[i], raw_state[i + 1]);
-        maybe_init(&mut state, key);
-        *state.get_mut(&key).unwrap() += 1;
-    }
-    // We alternate between two state maps to avoid allocating memory
-    // at each iteration.
-    let mut state_odd = state.clone();
-    let mut state_last = &state;
-    for i in 1..=40 {
-        if i % 2 == 0 {
-            evolve(&state, &mut state_odd, &rules);
-            state_last = &state_odd;
-        } else {
-            evolve(&state_odd, &mut state, &rules);
-            state_last = &state;
+type Count = HashMap<u8, u128>;
+
+#[derive(Clone, Debug)]
+struct Polymer {
+    rules: Rules,
+    last: u8,
+    pairs: State,
+    steps: u64,
+}
+
+impl Polymer {
+    fn new(template: &[u8], rules: Rules) -> Polymer {
+        let mut pairs = State::new();
+        for pair in template.windows(2) {
+            *pairs.entry((pair[0], pair[1])).or_insert(0) += 1;
+        }
+        Polymer {
+            rules,
+            last: *template.last().unwrap(),
+            pairs,
+            steps: 0,
         }
     }
-    let count = count_elems(&state_last, *first, *last);
-    print_count(&count);
-    count.values().max().unwrap() - count.values().min().unwrap()
+
+    /// Evolve n times.  Counts are exact, so this panics when they
+    /// overflow, after about 120 steps: use `elements_after` with a
+    /// modulus to go further.
+    fn step(&mut self, n: u64) {
+        for _ in 0..n {
+            self.pairs = evolve(&self.pairs, &self.rules);
+        }
+        self.steps += n;
+    }
+
+    /// The number of steps taken so far.
+    fn steps(&self) -> u64 {
+        self.steps
+    }
+
+    /// How many times each pair appears.
+    fn pairs(&self) -> &State {
+        &self.pairs
+    }
+
+    /// How many times each element appears.
+    fn elements(&self) -> Count {
+        count_elems(&self.pairs, self.last)
+    }
+
+    fn len(&self) -> u128 {
+        let mut ret = 1;
+        for count in self.pairs.values() {
+            add(&mut ret, *count);
+        }
+        ret
+    }
+
+    /// The element counts after n more steps, computed by matrix
+    /// exponentiation, exact or modulo `modulus` as described in
+    /// [`lib::matrix`].
+    fn elements_after(&self, n: u64, modulus: Option<u64>) -> Count {
+        let transition = Transition::new(self);
+        let vector: Vec<u128> = transition
+            .pairs
+            .iter()
+            .map(|p| *self.pairs.get(p).unwrap_or(&0))
+            .collect();
+        let vector = matrix::vec_mul_pow(&vector, &transition.matrix, n, modulus);
+
+        let state: State = transition.pairs.into_iter().zip(vector).collect();
+        let mut ret = count_elems(&state, self.last);
+        if let Some(m) = modulus {
+            for v in ret.values_mut() {
+                *v %= m as u128;
+            }
+        }
+        ret
+    }
+}
+
+/// The matrix of one evolution step, over every pair that can ever
+/// appear.  `matrix[i][j]` is how many pairs j one pair i becomes.
+struct Transition {
+    pairs: Vec<Pair>,
+    matrix: Matrix,
 }
 
-fn evolve(prev: &State, next: &mut State, rules: &Rules) {
-    // We reset the next state.
-    for v in next.values_mut() {
-        *v = 0;
+impl Transition {
+    fn new(polymer: &Polymer) -> Transition {
+        // Close the current pairs under the rules.
+        let mut pairs: BTreeSet<Pair> = polymer.pairs.keys().copied().collect();
+        let mut todo: Vec<Pair> = pairs.iter().copied().collect();
+        while let Some(pair @ (l, r)) = todo.pop() {
+            if let Some(new) = polymer.rules.get(&pair) {
+                for next in [(l, *new), (*new, r)] {
+                    if pairs.insert(next) {
+                        todo.push(next);
+                    }
+                }
+            }
+        }
+
+        let pairs: Vec<Pair> = pairs.into_iter().collect();
+        let index: HashMap<Pair, usize> = pairs.iter().enumerate().map(|(i, p)| (*p, i)).collect();
+        let mut matrix = vec![vec![0; pairs.len()]; pairs.len()];
+        for (i, pair @ (l, r)) in pairs.iter().enumerate() {
+            if let Some(new) = polymer.rules.get(pair) {
+                matrix[i][index[&(*l, *new)]] += 1;
+                matrix[i][index[&(*new, *r)]] += 1;
+            } else {
+                matrix[i][i] += 1;
+            }
+        }
+        Transition { pairs, matrix }
     }
+}
 
-    // Then, we iterate over the previous map.
+/// Add `count` to `total`, panicking on overflow like
+/// `matrix::mul_add` does.
+fn add(total: &mut u128, count: u128) {
+    *total = total.checked_add(count).expect("Overflow: use a modulus");
+}
+
+fn evolve(prev: &State, rules: &Rules) -> State {
+    let mut next = State::new();
     for (pair @ (l, r), count) in prev {
-        // @FIXME This is nitpicking, but that rules.get() in a loop
-        // could probably be optimized away, eg by having the rule map
-        // *and* the pairs map be defined for all possible pairs ---
-        // there's just 100 of them.
-        if let Some(new) = rules.get(&pair) {
-            maybe_init(next, (*l, *new));
-            *next.get_mut(&(*l, *new)).unwrap() += count;
-            maybe_init(next, (*new, *r));
-            *next.get_mut(&(*new, *r)).unwrap() += count;
+        if let Some(new) = rules.get(pair) {
+            add(next.entry((*l, *new)).or_insert(0), *count);
+            add(next.entry((*new, *r)).or_insert(0), *count);
         } else {
-            *next.get_mut(&pair).unwrap() += count;
+            add(next.entry(*pair).or_insert(0), *count);
         }
     }
+    next
 }
 
-fn count_elems(state: &State, first: u8, last: u8) -> Count {
+fn count_elems(state: &State, last: u8) -> Count {
     let mut ret: Count = Count::new();
-    ret.insert(first, 1);
     ret.insert(last, 1);
-    for ((l, r), count) in state {
-        // Fully process left, then right, in case left==right.
-        let l_count: u64 = *ret.get(l).unwrap_or(&0);
-        ret.insert(*l, count + l_count);
-        // Right
-        let r_count: u64 = *ret.get(r).unwrap_or(&0);
-        ret.insert(*r, count + r_count);
-    }
-    for v in ret.values_mut() {
-        *v /= 2
+    for ((l, _), count) in state {
+        add(ret.entry(*l).or_insert(0), *count);
     }
     ret
 }
 
-fn print_count(count: &Count) {
-    for (k, v) in count.iter() {
-        println!("{} = {}", show1(k), v);
-    }
+/// The difference between the most and least common elements.
+fn spread(count: &Count) -> u128 {
+    count.values().max().unwrap() - count.values().min().unwrap()
 }
 
-fn maybe_init(state: &mut State, key: Pair) {
-    if !state.contains_key(&key) {
-        state.insert(key, 0);
+fn print_count(count: &Count) {
+    let mut count: Vec<_> = count.iter().collect();
+    count.sort();
+    for (k, v) in count {
+        println!("{} = {}", show1(k), v);
     }
 }
 
@@ -140,6 +210,134 @@ fn read_input() -> (Vec<u8>, Rules) {
     (state, rules)
 }
 
+///////////////////////////////////////////////////////////////////////
+// VALIDATION AND ANALYSIS ////////////////////////////////////////////
+///////////////////////////////////////////////////////////////////////
+
+/// What's suspicious about a set of rules, for a given polymer.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+struct Validation {
+    /// Pairs of known elements that have no rule.  They're inert:
+    /// evolution leaves them alone.
+    missing_rules: Vec<Pair>,
+    /// Elements the rules know about, but that will never appear in
+    /// the polymer.
+    unreachable: Vec<u8>,
+    /// Pairs that can appear, but whose descendants eventually all
+    /// become inert, so they stop growing the polymer.
+    finite: Vec<Pair>,
+}
+
+impl Validation {
+    fn print(&self) {
+        let pairs = |pairs: &[Pair]| {
+            pairs
+                .iter()
+                .map(|(l, r)| show(&[*l, *r]))
+                .collect::<Vec<_>>()
+                .join(" ")
+        };
+        println!("Pairs without rules: {}", pairs(&self.missing_rules));
+        println!("Unreachable elements: {}", show(&self.unreachable));
+        println!("Pairs that stop growing: {}", pairs(&self.finite));
+    }
+}
+
+impl Polymer {
+    fn validate(&self) -> Validation {
+        let mut known: BTreeSet<u8> = BTreeSet::new();
+        known.insert(self.last);
+        for ((l, r), new) in &self.rules {
+            known.extend([*l, *r, *new]);
+        }
+        for (l, r) in self.pairs.keys() {
+            known.extend([*l, *r]);
+        }
+
+        let mut missing_rules = vec![];
+        for l in &known {
+            for r in &known {
+                if !self.rules.contains_key(&(*l, *r)) {
+                    missing_rules.push((*l, *r));
+                }
+            }
+        }
+
+        let reachable = Transition::new(self).pairs;
+        let mut seen: BTreeSet<u8> = reachable.iter().map(|p| p.0).collect();
+        seen.insert(self.last);
+        let unreachable = known.difference(&seen).copied().collect();
+
+        // A pair is finite if it's inert, or if both the pairs it
+        // becomes are finite.  Cycles are infinite, so we only
+        // accept a pair once both its children have been accepted.
+        let mut finite: BTreeSet<Pair> = reachable
+            .iter()
+            .filter(|p| !self.rules.contains_key(p))
+            .copied()
+            .collect();
+        let mut again = true;
+        while again {
+            again = false;
+            for pair @ (l, r) in &reachable {
+                if finite.contains(pair) {
+                    continue;
+                }
+                let new = self.rules[pair];
+                if finite.contains(&(*l, new)) && finite.contains(&(new, *r)) {
+                    finite.insert(*pair);
+                    again = true;
+                }
+            }
+        }
+
+        Validation {
+            missing_rules,
+            unreachable,
+            finite: finite.into_iter().collect(),
+        }
+    }
+
+    /// Check whether the distribution of pairs (the share of each
+    /// pair in the polymer) converges, by iterating evolution on the
+    /// normalized distribution until it moves less than `epsilon`.
+    /// Returns the step it converged at and the stationary shares, or
+    /// None if it didn't within `max_steps`.  A single-element polymer
+    /// has no pairs, and never changes: it's stationary from step 0,
+    /// with no shares.
+    fn stationary(&self, epsilon: f64, max_steps: u64) -> Option<(u64, Vec<(Pair, f64)>)> {
+        if self.pairs.is_empty() {
+            return Some((0, vec![]));
+        }
+        let transition = Transition::new(self);
+        let total: f64 = self.pairs.values().map(|v| *v as f64).sum();
+        let mut shares: Vec<f64> = transition
+            .pairs
+            .iter()
+            .map(|p| *self.pairs.get(p).unwrap_or(&0) as f64 / total)
+            .collect();
+
+        for step in 1..=max_steps {
+            let mut next = vec![0.0; shares.len()];
+            for (i, share) in shares.iter().enumerate() {
+                for (j, m) in transition.matrix[i].iter().enumerate() {
+                    next[j] += share * *m as f64;
+                }
+            }
+            let total: f64 = next.iter().sum();
+            for v in next.iter_mut() {
+                *v /= total;
+            }
+            let moved: f64 = next.iter().zip(&shares).map(|(a, b)| (a - b).abs()).sum();
+            shares = next;
+            if moved < epsilon {
+                return Some((step, transition.pairs.into_iter().zip(shares).collect()));
+            }
+        }
+        None
+    }
+}
+
 ///////////////////////////////////////////////////////////////////////
 // STUPID FIRST VERSION (PART 1) //////////////////////////////////////
 ///////////////////////////////////////////////////////////////////////
@@ -157,44 +355,17 @@ fn stupid_evolve(state: &[u8], rules: &HashMap<(u8, u8), u8>) -> Vec<u8> {
     ret
 }
 
-fn stupid_compute() {
-    let mut input = read_lines("../inputs/14.txt").unwrap();
-    let mut rules: HashMap<(u8, u8), u8> = HashMap::new();
-
-    // Read initial state
-    let mut state = input.next().unwrap().unwrap().into_bytes();
-    println!("Starting from {:?}", show(&state));
-
-    // Read rules
-    for raw in input.skip(1) {
-        let line = raw.unwrap().into_bytes();
-        let pair = (line[0], line[1]);
-        let new = line[6];
-        rules.insert(pair, new);
-    }
-
-    for i in 1..=10 {
-        state = stupid_evolve(&state.as_slice(), &rules);
-        println!("After {} evolutions, it measures {}", i, state.len());
+/// Count elements the hard way, by actually building the polymer.
+fn stupid_count(template: &[u8], rules: &Rules, steps: u64) -> Count {
+    let mut state = template.to_vec();
+    for _ in 0..steps {
+        state = stupid_evolve(&state, rules);
     }
-    state.sort();
-    let mut current = state[0];
-    let mut count = 0;
-    let mut counts = vec![];
+    let mut ret = Count::new();
     for b in state {
-        if b == current {
-            count += 1
-        } else {
-            counts.push(count);
-            current = b;
-            count = 1;
-        }
+        *ret.entry(b).or_insert(0) += 1;
     }
-    counts.push(count);
-    println!(
-        "{}",
-        counts.iter().max().unwrap() - counts.iter().min().unwrap()
-    );
+    ret
 }
 
 ///////////////////////////////////////////////////////////////////////
@@ -210,8 +381,139 @@ fn show1(bytes: &u8) -> String {
 }
 
 fn main() {
-    stupid_compute();
-    println!("Part 2: {}", compute());
+    let (template, rules) = read_input();
+    let mut polymer = Polymer::new(&template, rules);
+    polymer.validate().print();
+    match polymer.stationary(1e-12, 1000) {
+        Some((step, _)) => println!("Pair distribution converges after {} steps", step),
+        None => println!("Pair distribution doesn't converge"),
+    }
+
+    polymer.step(10);
+    assert_eq!(
+        polymer.elements(),
+        stupid_count(&template, &polymer.rules, 10)
+    );
+    println!("Part 1: {}", spread(&polymer.elements()));
+
+    polymer.step(30);
+    print_count(&polymer.elements());
+    println!(
+        "After {} steps, the polymer measures {}",
+        polymer.steps(),
+        polymer.len()
+    );
+    println!("Part 2: {}", spread(&polymer.elements()));
+
+    // --pairs prints the pair histogram, --steps N computes element
+    // counts after N steps, modulo a large prime.
+    let args: Vec<String> = std::env::args().collect();
+    if args.iter().any(|a| a == "--pairs") {
+        let mut pairs: Vec<_> = polymer.pairs().iter().collect();
+        pairs.sort();
+        for ((l, r), count) in pairs {
+            println!("{}{} = {}", show1(l), show1(r), count);
+        }
+    }
+    for pair in args.windows(2) {
+        if pair[0] == "--steps" {
+            let n: u64 = pair[1].parse().expect("--steps wants a number");
+            let start = Polymer::new(&template, polymer.rules.clone());
+            println!("After {} steps, modulo 1000000007:", n);
+            print_count(&start.elements_after(n, Some(1_000_000_007)));
+        }
+    }
 }
 
-//
+#[cfg(test)]
+fn example() -> (Vec<u8>, Rules) {
+    let template = b"NNCB".to_vec();
+    let rules = [
+        "CH -> B", "HH -> N", "CB -> H", "NH -> C", "HB -> C", "HC -> B", "HN -> C", "NN -> C",
+        "BH -> H", "NC -> B", "NB -> B", "BN -> B", "BB -> N", "BC -> B", "CC -> N", "CN -> C",
+    ]
+    .iter()
+    .map(|r| {
+        let r = r.as_bytes();
+        ((r[0], r[1]), r[6])
+    })
+    .collect();
+    (template, rules)
+}
+
+#[test]
+fn test_polymer() {
+    let (template, rules) = example();
+    let mut polymer = Polymer::new(&template, rules);
+    for n in 1..=10 {
+        polymer.step(1);
+        assert_eq!(
+            polymer.elements(),
+            stupid_count(&template, &polymer.rules, n)
+        );
+    }
+    assert_eq!(polymer.len(), 3073);
+    assert_eq!(spread(&polymer.elements()), 1588);
+    polymer.step(30);
+    assert_eq!(spread(&polymer.elements()), 2188189693529);
+}
+
+#[test]
+#[should_panic(expected = "Overflow")]
+fn test_step_overflow() {
+    let (template, rules) = example();
+    Polymer::new(&template, rules).step(200);
+}
+
+#[test]
+fn test_elements_after() {
+    let (template, rules) = example();
+    let start = Polymer::new(&template, rules);
+    let mut polymer = start.clone();
+    polymer.step(40);
+    assert_eq!(start.elements_after(40, None), polymer.elements());
+
+    let modulus = 1_000_000_007;
+    let reduced: Count = polymer
+        .elements()
+        .into_iter()
+        .map(|(k, v)| (k, v % modulus as u128))
+        .collect();
+    assert_eq!(start.elements_after(40, Some(modulus)), reduced);
+}
+
+#[test]
+fn test_validate() {
+    let (template, rules) = example();
+    let report = Polymer::new(&template, rules.clone()).validate();
+    assert_eq!(report, Validation::default());
+
+    // Without a rule for NN, nothing ever grows from the template's
+    // NN, and nothing else produces it.
+    let mut rules = rules;
+    rules.remove(&(b'N', b'N'));
+    rules.insert((b'X', b'Y'), b'Z');
+    let report = Polymer::new(b"NNCB", rules).validate();
+    assert!(report.missing_rules.contains(&(b'N', b'N')));
+    assert!(report.missing_rules.contains(&(b'X', b'X')));
+    assert_eq!(report.unreachable, b"XYZ".to_vec());
+    assert!(report.finite.contains(&(b'N', b'N')));
+    assert!(!report.finite.contains(&(b'N', b'C')));
+}
+
+#[test]
+fn test_stationary() {
+    let (template, rules) = example();
+    let polymer = Polymer::new(&template, rules);
+    let (_, shares) = polymer.stationary(1e-12, 1000).unwrap();
+    assert!((shares.iter().map(|s| s.1).sum::<f64>() - 1.0).abs() < 1e-9);
+
+    // Inert pairs don't move at all.
+    let polymer = Polymer::new(b"AB", Rules::new());
+    assert_eq!(polymer.stationary(1e-12, 10).unwrap().0, 1);
+
+    // A single element has no pairs at all.
+    let (_, rules) = example();
+    let polymer = Polymer::new(b"N", rules);
+    assert_eq!(polymer.stationary(1e-12, 10), Some((0, vec![])));
+}
@@ -1,188 +1,176 @@
 // Looking for an optimal path.
 //
-// Naive approach: systematic enumeration (this is the only way this
-// time) *but* we try the shortest path first, and abandon all paths
-// with a higher risk level.
-//
-// It means that at each point, we move from the direction that will
-// bring us the closest to target, to the direction that will bring up
-// farthest.
+// The maze is a grid, so we run Dijkstra's algorithm right on it:
+// neighbours are the four cells around, and moving costs the risk
+// of the cell we enter.
 
+use ansi_term::Colour;
+use lib::term::Painter;
 use lib::*;
-use std::collections::{HashMap, HashSet};
-use std::fmt::Debug;
-use std::hash::Hash;
-
-fn dijkstra(graph: &Graph<usize>, start: usize, to: usize) -> HashMap<usize, usize> {
-    let mut dist: HashMap<usize, u32> = HashMap::new();
-    let mut prev: HashMap<usize, usize> = HashMap::new();
-    let mut nodes = graph.nodes.clone();
-
-    dist.insert(start, 0);
-
-    println!("Now to the main loop;");
-    loop {
-        // println!("Dists are {:?}", dist);
-        let u = nodes
-            .iter()
-            .filter(|node| dist.get(node).is_some())
-            .min_by_key(|node| dist.get(node).unwrap());
-
-        if u.is_none() {
-            break;
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashSet};
+
+type Coords = (isize, isize);
+
+/// The least risky path from `from` to `to`, if there's one: its total
+/// risk, and the cells it goes through, both ends included.  The
+/// risk of the starting cell isn't counted, since we never enter it.
+fn shortest_path(maze: &Vec2D<u8>, from: Coords, to: Coords) -> Option<(u32, Vec<Coords>)> {
+    let mut dist: Vec<Option<u32>> = vec![None; maze.vec.len()];
+    let mut prev: Vec<Option<usize>> = vec![None; maze.vec.len()];
+    let mut queue = BinaryHeap::new();
+
+    let start = maze.to_index(from);
+    let end = maze.to_index(to);
+    dist[start] = Some(0);
+    queue.push(Reverse((0, start)));
+
+    while let Some(Reverse((risk, u))) = queue.pop() {
+        if u == end {
+            let mut path = vec![to];
+            let mut current = end;
+            while let Some(p) = prev[current] {
+                path.push(maze.to_coords(p));
+                current = p;
+            }
+            path.reverse();
+            return Some((risk, path));
         }
-        let u = *u.unwrap();
-
-        nodes.remove(&u);
-
-        for edge in graph.edges.iter().filter(|e| e.is_from(&u)) {
-            let v = edge.b;
-            if nodes.contains(&v) {
-                let alt = dist.get(&u).unwrap() + edge.cost;
-                if let Some(v_dist) = dist.get(&v) {
-                    if alt < *v_dist {
-                        dist.insert(v, alt);
-                        prev.insert(v, u);
-                    }
-                } else {
-                    dist.insert(v, alt);
-                    prev.insert(v, u);
-                    if v % 50 == 0 {
-                        println!("Explored {}: {}", v, alt);
-                    }
+        if dist[u].is_some_and(|d| d < risk) {
+            continue; // Stale entry, we've found better since.
+        }
+        let (x, y) = maze.to_coords(u);
+        for (dx, dy) in [(-1, 0), (1, 0), (0, -1), (0, 1)] {
+            if let Some(cost) = maze.safe_index(x + dx, y + dy) {
+                let v = maze.to_index((x + dx, y + dy));
+                let alt = risk + cost as u32;
+                if dist[v].is_none_or(|d| alt < d) {
+                    dist[v] = Some(alt);
+                    prev[v] = Some(u);
+                    queue.push(Reverse((alt, v)));
                 }
             }
         }
     }
-    println!("{:?}", dist.get(&to));
-    prev
+    None
 }
 
-fn main() {
-    let mut maze = multiply_input(&read_input());
-    let mut graph = Graph::<usize>::from_vec2d(&maze);
-
-    let from = maze.to_index((0, 0));
-    let to = maze.to_index(((maze.height() - 1) as isize, (maze.width() - 1) as isize));
-    let prevs = dijkstra(&mut graph, from, to);
-
-    let mut current = to;
-    loop {
-        maze.vec[current] = 10;
-        if !prevs.contains_key(&current) {
-            break;
+/// Draw the maze with the path highlighted, or marked with X if we
+/// can't use colours.
+fn draw_path(maze: &Vec2D<u8>, path: &HashSet<usize>, painter: &Painter) {
+    for (i, risk) in maze.vec.iter().enumerate() {
+        if i % maze.width() == 0 {
+            println!();
+        }
+        if !path.contains(&i) {
+            print!("{}", painter.paint(Colour::Fixed(244), risk));
+        } else if painter.enabled() {
+            print!("{}", painter.paint(Colour::Red.bold(), risk));
+        } else {
+            print!("X");
         }
-        current = *prevs.get(&current).unwrap();
     }
-    maze.draw();
-}
-
-#[derive(PartialEq, Eq, Copy, Clone, Debug, Hash, PartialOrd, Ord)]
-struct Edge<T: Debug + PartialEq + Eq + Copy + Hash + PartialOrd> {
-    a: T,
-    b: T,
-    cost: u32,
+    println!();
 }
 
-impl<T: Debug + PartialEq + Eq + Copy + Hash + PartialOrd + Ord> Edge<T> {
-    pub fn new(a: T, b: T, cost: u32) -> Edge<T> {
-        Edge { a, b, cost }
-    }
-
-    pub fn is_from(&self, t: &T) -> bool {
-        self.a == *t
+fn main() {
+    let small = read_input();
+    let maze = tile(&small, 5, &risk_wrap);
+
+    let mut path = HashSet::new();
+    for (part, maze) in [(1, &small), (2, &maze)] {
+        let to = (maze.width() as isize - 1, maze.height() as isize - 1);
+        let (risk, coords) = shortest_path(maze, (0, 0), to).unwrap();
+        println!("Part {}: {}", part, risk);
+        path = coords.into_iter().map(|c| maze.to_index(c)).collect();
     }
+    draw_path(&maze, &path, &Painter::from_args());
 }
 
-#[derive(PartialEq, Eq, Clone)]
-struct Graph<E: Debug + Copy + Eq + PartialEq + PartialOrd + Hash> {
-    nodes: HashSet<E>,
-    edges: HashSet<Edge<E>>,
+/// Risk levels go up by one for each tile away from the original,
+/// wrapping from 9 back to 1.
+fn risk_wrap(risk: u8, distance: usize) -> u8 {
+    ((risk as usize - 1 + distance) % 9 + 1) as u8
 }
 
-impl<E: Copy + Eq + PartialEq + Hash + PartialOrd + Debug> Graph<E> {
-    fn new() -> Graph<E> {
-        Graph {
-            nodes: HashSet::new(),
-            edges: HashSet::new(),
-        }
-    }
-
-    fn from_vec2d(vec: &Vec2D<u8>) -> Graph<usize> {
-        if vec.height() != vec.width() {
-            panic!("I wanted a square graph!");
-        }
-
-        let mut ret = Graph::<usize>::new();
-
-        for i in 0..vec.width() as isize {
-            for j in 0..=i {
-                for (x, y) in [(i, j), (j, i)] {
-                    let this_cost = vec[(x, y)] as u32;
-                    let id = vec.to_index((x, y));
-                    ret.nodes.insert(id);
-                    if x > 0 {
-                        ret.edges.insert(Edge::new(
-                            id,
-                            vec.to_index((x - 1, y)),
-                            vec[(x - 1, y)] as u32,
-                        ));
-                        ret.edges
-                            .insert(Edge::new(vec.to_index((x - 1, y)), id, this_cost));
-                    }
-                    if y > 0 {
-                        ret.edges.insert(Edge::new(
-                            id,
-                            vec.to_index((x, y - 1)),
-                            vec[(x, y - 1)] as u32,
-                        ));
-                        ret.edges
-                            .insert(Edge::new(vec.to_index((x, y - 1)), id, this_cost));
-                    }
-                }
-            }
-        }
-        ret
-    }
-}
-
-fn multiply_input(vec: &Vec2D<u8>) -> Vec2D<u8> {
+/// Repeat `vec` `factor` times in both directions.  Each tile's
+/// values are computed from the original ones by `wrap`, given how
+/// many tiles right and down from the original it is.
+fn tile(vec: &Vec2D<u8>, factor: usize, wrap: &dyn Fn(u8, usize) -> u8) -> Vec2D<u8> {
     let w1 = vec.width() as isize;
     let h1 = vec.height() as isize;
-    let w2 = w1 * 5_isize;
-    let h2 = h1 * 5_isize;
-    let mut ret = Vec2D::<u8>::new(w2 as usize, h2 as usize, 0);
-
-    for x in 0..w2 {
-        for y in 0..h2 {
-            let nx = x / w1;
-            let ny = y / h1;
-            let mut val = vec[(x % w1, y % h1)] + (nx as u8) + (ny as u8);
-            if val > 9 {
-                val %= 9
-            }
-            ret[(x as isize, y as isize)] = val
+    let mut ret = Vec2D::<u8>::new(vec.width() * factor, vec.height() * factor, 0);
+
+    for x in 0..ret.width() as isize {
+        for y in 0..ret.height() as isize {
+            let distance = (x / w1 + y / h1) as usize;
+            ret[(x, y)] = wrap(vec[(x % w1, y % h1)], distance);
         }
     }
     ret
 }
 
+/// Read a grid of digits, one row per line.
+fn parse_grid<S: AsRef<str>>(lines: &[S]) -> Vec2D<u8> {
+    let rows: Vec<Vec<u8>> = lines
+        .iter()
+        .map(|l| l.as_ref().chars().map(read_digit).collect())
+        .collect();
+    Vec2D::from_rows(&rows)
+}
+
 fn read_input() -> Vec2D<u8> {
     let lines: Vec<String> = read_lines("../inputs/15.txt")
         .unwrap()
         .map(&std::result::Result::unwrap)
         .collect();
-    let mut ret = Vec2D::new(lines[0].len(), lines.len(), 0);
-    for y in 0..lines.len() {
-        for x in 0..lines[y].len() {
-            ret[(x as isize, y as isize)] = lines[y]
-                .chars()
-                .nth(x)
-                .unwrap()
-                .to_string()
-                .parse::<u8>()
-                .unwrap();
-        }
-    }
-    ret
+    parse_grid(&lines)
+}
+
+#[test]
+fn test_shortest_path() {
+    let rows = [
+        "1163751742",
+        "1381373672",
+        "2136511328",
+        "3694931569",
+        "7463417111",
+        "1319128137",
+        "1359912421",
+        "3125421639",
+        "1293138521",
+        "2311944581",
+    ];
+    let maze = parse_grid(&rows);
+    let (risk, path) = shortest_path(&maze, (0, 0), (9, 9)).unwrap();
+    assert_eq!(risk, 40);
+    assert_eq!(path.first(), Some(&(0, 0)));
+    assert_eq!(path.last(), Some(&(9, 9)));
+    assert_eq!(path[1..].iter().map(|c| maze[*c] as u32).sum::<u32>(), risk);
+
+    let big = tile(&maze, 5, &risk_wrap);
+    assert_eq!((big.width(), big.height()), (50, 50));
+    assert_eq!(big[(49, 49)], 9);
+    assert_eq!(big[(10, 0)], 2);
+    assert_eq!(shortest_path(&big, (0, 0), (49, 49)).unwrap().0, 315);
+
+    // Not square, and a wall of 9s to go around.
+    let mut maze = Vec2D::new(5, 2, 1);
+    maze[(1, 0)] = 9;
+    maze[(3, 1)] = 9;
+    let (risk, path) = shortest_path(&maze, (0, 0), (4, 1)).unwrap();
+    assert_eq!(risk, 7);
+    assert_eq!(
+        path,
+        vec![
+            (0, 0),
+            (0, 1),
+            (1, 1),
+            (2, 1),
+            (2, 0),
+            (3, 0),
+            (4, 0),
+            (4, 1)
+        ]
+    );
 }
@@ -1,75 +1,238 @@
 use lib::*;
 
+use std::fmt;
 use std::str::FromStr;
 
-enum Day2Direction {
-    Forward,
-    Down,
-    Up,
+/// A command we understand: its name, whether it takes a value, and
+/// what it does to a submarine with that value.
+struct CommandSpec {
+    name: &'static str,
+    takes_value: bool,
+    action: fn(&mut dyn Submarine, i64),
 }
 
-struct Day2A {
-    direction: Day2Direction,
-    value: u32,
+/// The commands we understand.  New commands go here, built from the
+/// moves every submarine knows.
+const COMMANDS: [CommandSpec; 5] = [
+    CommandSpec {
+        name: "forward",
+        takes_value: true,
+        action: |s, v| s.advance(v),
+    },
+    CommandSpec {
+        name: "back",
+        takes_value: true,
+        action: |s, v| s.advance(-v),
+    },
+    CommandSpec {
+        name: "down",
+        takes_value: true,
+        action: |s, v| s.down(v),
+    },
+    CommandSpec {
+        name: "up",
+        takes_value: true,
+        action: |s, v| s.down(-v),
+    },
+    CommandSpec {
+        name: "surface",
+        takes_value: false,
+        action: |s, _| s.surface(),
+    },
+];
+
+#[derive(Clone, Copy)]
+struct Command {
+    spec: &'static CommandSpec,
+    value: i64,
+}
+
+impl Command {
+    fn apply(&self, submarine: &mut dyn Submarine) {
+        (self.spec.action)(submarine, self.value)
+    }
 }
 
-impl FromStr for Day2A {
+impl fmt::Debug for Command {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.spec.takes_value {
+            write!(f, "{} {}", self.spec.name, self.value)
+        } else {
+            write!(f, "{}", self.spec.name)
+        }
+    }
+}
+
+impl FromStr for Command {
     type Err = ();
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         let mut s = s.split(' ');
-        let direction = match s.next().ok_or(())? {
-            "forward" => Some(Day2Direction::Forward),
-            "up" => Some(Day2Direction::Up),
-            "down" => Some(Day2Direction::Down),
-            &_ => None,
+        let name = s.next().ok_or(())?;
+        let spec = COMMANDS.iter().find(|c| c.name == name).ok_or(())?;
+        let value = if spec.takes_value {
+            s.next().ok_or(())?.parse::<i64>().map_err(|_| ())?
+        } else {
+            0
+        };
+        if s.next().is_some() {
+            return Err(());
         }
-        .ok_or(())?;
-        let value = s.next().ok_or(())?.parse::<u32>().map_err(|_| ())?;
 
-        Ok(Day2A { direction, value })
+        Ok(Command { spec, value })
     }
 }
 
-fn day2a() -> u32 {
-    let mut h = 0;
-    let mut d = 0;
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+struct Position {
+    horizontal: i64,
+    depth: i64,
+}
 
-    for l in read_lines("../inputs/2.txt")
-        .expect("no read")
-        .map(|l| l.expect("no line").parse::<Day2A>().expect("no parse"))
-    {
-        match l.direction {
-            Day2Direction::Forward => h += l.value,
-            Day2Direction::Down => d += l.value,
-            Day2Direction::Up => d -= l.value,
-        }
+/// The moves a submarine knows, which commands are made of.
+trait Submarine {
+    /// Move forward, or back if `distance` is negative.
+    fn advance(&mut self, distance: i64);
+    /// Go down, or up if `value` is negative.
+    fn down(&mut self, value: i64);
+    /// Go back to the surface.
+    fn surface(&mut self);
+    fn position(&self) -> Position;
+}
+
+/// The submarine as we first understood it: up and down change
+/// depth directly.
+#[derive(Default)]
+struct Plain {
+    position: Position,
+}
+
+impl Submarine for Plain {
+    fn advance(&mut self, distance: i64) {
+        self.position.horizontal += distance;
+    }
+
+    fn down(&mut self, value: i64) {
+        self.position.depth += value;
+    }
+
+    fn surface(&mut self) {
+        self.position.depth = 0;
+    }
+
+    fn position(&self) -> Position {
+        self.position
     }
-    h * d
 }
 
-fn day2b() -> u32 {
-    let mut h = 0;
-    let mut d = 0;
-    let mut aim = 0;
+/// The submarine as the manual describes it: up and down change the
+/// aim, and moving follows it.
+#[derive(Default)]
+struct Aimed {
+    position: Position,
+    aim: i64,
+}
+
+impl Submarine for Aimed {
+    fn advance(&mut self, distance: i64) {
+        self.position.horizontal += distance;
+        self.position.depth += self.aim * distance;
+    }
+
+    fn down(&mut self, value: i64) {
+        self.aim += value;
+    }
+
+    fn surface(&mut self) {
+        self.position.depth = 0;
+        self.aim = 0;
+    }
+
+    fn position(&self) -> Position {
+        self.position
+    }
+}
 
-    for l in read_lines("../inputs/2.txt")
+/// Run every command, and return the positions the submarine went
+/// through, starting with the initial one.
+fn run(submarine: &mut dyn Submarine, commands: &[Command]) -> Vec<Position> {
+    let mut ret = vec![submarine.position()];
+    for command in commands {
+        command.apply(submarine);
+        ret.push(submarine.position());
+    }
+    ret
+}
+
+fn main() {
+    let commands: Vec<Command> = read_lines("../inputs/2.txt")
         .expect("no read")
-        .map(|l| l.expect("no line").parse::<Day2A>().expect("no parse"))
-    {
-        match l.direction {
-            Day2Direction::Forward => {
-                h += l.value;
-                d += aim * l.value;
+        .map(|l| l.expect("no line").parse::<Command>().expect("no parse"))
+        .collect();
+    // With --log, print the whole trajectory.
+    let log = std::env::args().any(|a| a == "--log");
+
+    let submarines: [(&str, &mut dyn Submarine); 2] = [
+        ("part 1", &mut Plain::default()),
+        ("part 2", &mut Aimed::default()),
+    ];
+    for (name, submarine) in submarines {
+        let trajectory = run(submarine, &commands);
+        if log {
+            for (command, position) in commands.iter().zip(trajectory.iter().skip(1)) {
+                println!("{:?} -> {:?}", command, position);
             }
-            Day2Direction::Down => aim += l.value,
-            Day2Direction::Up => aim -= l.value,
         }
+        let end = trajectory.last().unwrap();
+        println!("Day 2 ({}): {}", name, end.horizontal * end.depth);
     }
-    h * d
 }
 
-fn main() {
-    println!("Day 2 (part 1): {}", day2a());
-    println!("Day 2 (part 2): {}", day2b());
+#[test]
+fn test_submarines() {
+    let commands: Vec<Command> = [
+        "forward 5",
+        "down 5",
+        "forward 8",
+        "up 3",
+        "down 8",
+        "forward 2",
+    ]
+    .iter()
+    .map(|c| c.parse().unwrap())
+    .collect();
+    let end = *run(&mut Plain::default(), &commands).last().unwrap();
+    assert_eq!(end.horizontal * end.depth, 150);
+    let trajectory = run(&mut Aimed::default(), &commands);
+    assert_eq!(trajectory.len(), 7);
+    let end = trajectory.last().unwrap();
+    assert_eq!(end.horizontal * end.depth, 900);
+
+    // Going up too far no longer underflows, and we can undo moves.
+    let mut submarine = Plain::default();
+    "up 3".parse::<Command>().unwrap().apply(&mut submarine);
+    assert_eq!(submarine.position().depth, -3);
+    let mut submarine = Aimed::default();
+    for c in ["down 2", "forward 3", "back 3"] {
+        c.parse::<Command>().unwrap().apply(&mut submarine);
+    }
+    assert_eq!(submarine.position(), Position::default());
+    "forward 1"
+        .parse::<Command>()
+        .unwrap()
+        .apply(&mut submarine);
+    "surface".parse::<Command>().unwrap().apply(&mut submarine);
+    assert_eq!(submarine.position().depth, 0);
+    assert_eq!(submarine.aim, 0);
+}
+
+#[test]
+fn test_bad_commands() {
+    assert!("sideways 3".parse::<Command>().is_err());
+    assert!("forward".parse::<Command>().is_err());
+    assert!("surface 3".parse::<Command>().is_err());
+    assert_eq!(
+        format!("{:?}", "back 2".parse::<Command>().unwrap()),
+        "back 2"
+    );
 }
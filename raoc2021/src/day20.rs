@@ -1,103 +1,147 @@
+use lib::render::{pad, FrameWriter};
 use lib::*;
 
-/// That's how we cheat the "infinite image" rule.
+/// An image on the infinite plane.
 ///
-///  1. We load the image with extra pixels on each margin, more than
-///     there are steps.
-///
-///  2. When we need to read a pixel outside the image boundaries, we
-///     fall back to the pixel at (0,0), which is the "background",
-///     which alternates at each iteration step, because algorithm[0]
-///     == true and algorith[511] == false; so each iteration toggles
-///     the background color.
-fn get_or_tl(image: &Vec2D<bool>, x: isize, y: isize, ret: usize) -> usize {
-    if match image.safe_index(x, y) {
-        Some(x) => x,
-        None => image[(0, 0)],
-    } {
-        ret
-    } else {
-        0
-    }
+/// Only a finite region is actually stored; every pixel outside of
+/// it has the same value, the `background`.  Each enhancement step
+/// grows the region by one pixel on each side (that's as far as the
+/// influence of the finite region can reach) and computes the new
+/// background from the old one: an all-dark neighborhood maps to
+/// `alg[0]`, an all-lit one to `alg[511]`.
+struct InfiniteImage {
+    pixels: Vec2D<bool>,
+    background: bool,
 }
 
-fn enhance(alg: &Vec<bool>, image: &Vec2D<bool>) -> Vec2D<bool> {
-    let mut ret = image.clone();
-    let mut index;
-    for x in 0..image.width() as isize {
-        for y in 0..image.height() as isize {
-            index = get_or_tl(&image, x - 1, y - 1, 256)
-                + get_or_tl(&image, x, y - 1, 128)
-                + get_or_tl(&image, x + 1, y - 1, 64)
-                + get_or_tl(&image, x - 1, y, 32)
-                + get_or_tl(&image, x, y, 16)
-                + get_or_tl(&image, x + 1, y, 8)
-                + get_or_tl(&image, x - 1, y + 1, 4)
-                + get_or_tl(&image, x, y + 1, 2)
-                + get_or_tl(&image, x + 1, y + 1, 1);
-            ret[(x, y)] = alg[index];
+impl InfiniteImage {
+    fn get(&self, x: isize, y: isize) -> bool {
+        self.pixels.safe_index(x, y).unwrap_or(self.background)
+    }
+
+    /// Read the 3x3 neighborhood centered on (x, y) as a 9-bit
+    /// number, top-left pixel being the most significant bit.
+    fn index_at(&self, x: isize, y: isize) -> usize {
+        let mut index = 0;
+        for dy in -1..=1 {
+            for dx in -1..=1 {
+                index = (index << 1) | self.get(x + dx, y + dy) as usize;
+            }
         }
+        index
     }
-    ret
-}
 
-fn draw(image: &Vec2D<bool>) {
-    for y in 0..image.height() as isize {
-        for x in 0..image.width() as isize {
-            print!("{}", if image[(x, y)] { "#" } else { "." })
+    fn enhance(&self, alg: &[bool]) -> InfiniteImage {
+        let mut pixels = Vec2D::new(self.pixels.width() + 2, self.pixels.height() + 2, false);
+        for y in 0..pixels.height() as isize {
+            for x in 0..pixels.width() as isize {
+                // New pixel (x, y) is old pixel (x - 1, y - 1).
+                pixels[(x, y)] = alg[self.index_at(x - 1, y - 1)];
+            }
+        }
+        let background = if self.background { alg[511] } else { alg[0] };
+        InfiniteImage { pixels, background }
+    }
+
+    /// The number of lit pixels, or None if there's an infinity of
+    /// them.
+    fn count_lit(&self) -> Option<usize> {
+        if self.background {
+            None
+        } else {
+            Some(self.pixels.vec.iter().filter(|p| **p).count())
         }
-        println!();
     }
 }
 
-fn read_input(s: &str, side: usize, extra_pixels: usize) -> (Vec<bool>, Vec2D<bool>) {
-    let mut input = read_lines(s).unwrap();
-    let alg = input
-        .next()
-        .unwrap()
-        .unwrap()
-        .chars()
-        .map(|c| c == '#')
+fn parse_input(input: &str) -> (Vec<bool>, InfiniteImage) {
+    let mut lines = input.lines();
+    let alg = lines.next().unwrap().chars().map(|c| c == '#').collect();
+
+    let rows: Vec<Vec<bool>> = lines
+        .filter(|line| !line.is_empty())
+        .map(|line| line.chars().map(|c| c == '#').collect())
         .collect();
 
-    let mut image = Vec2D::<bool>::new(side + extra_pixels * 2, side + extra_pixels * 2, false);
-    println!("{},{}", image.height(), image.width());
+    (
+        alg,
+        InfiniteImage {
+            pixels: Vec2D::from_rows(&rows),
+            background: false,
+        },
+    )
+}
+
+fn main() {
+    let (alg, mut image) = parse_input(&std::fs::read_to_string("../inputs/20.txt").unwrap());
+    let mut frames = FrameWriter::from_args("day20");
+
+    // Each step grows the image by one pixel on each side, so frames
+    // are drawn on a canvas the size of the last one.
+    let steps = 50;
+    let width = image.pixels.width() + 2 * steps;
+    let height = image.pixels.height() + 2 * steps;
 
-    let mut y = extra_pixels as isize;
-    let mut x;
-    for line in input.skip(1) {
-        let line = line.unwrap();
-        // println!("{} {}", y, &line.len());
-        x = extra_pixels as isize;
-        for c in line.chars() {
-            image[(x, y)] = if c == '#' { true } else { false };
-            x += 1;
+    for i in 1..=steps {
+        image = image.enhance(&alg);
+        if let Some(frames) = &mut frames {
+            let margin = (steps - i) as isize;
+            let canvas = pad(
+                &image.pixels,
+                width,
+                height,
+                (margin, margin),
+                image.background,
+            );
+            frames.pgm(&canvas, &|p| if *p { 255 } else { 0 }).unwrap();
+        }
+        if i == 2 || i == steps {
+            match image.count_lit() {
+                Some(count) => println!("Lit pixels after {} steps: {}", i, count),
+                None => println!("Lit pixels after {} steps: infinitely many", i),
+            }
         }
-        println!();
-        y += 1;
     }
-
-    (alg, image)
 }
 
-fn main() {
-    let (alg, mut image) = read_input("../inputs/20.txt", 100, 60);
-    print!("Stepped enhancement…");
-
+#[test]
+fn test_enhance() {
+    let (alg, mut image) = parse_input(concat!(
+        "..#.#..#####.#.#.#.###.##.....###.##.#..###.####..#####..#....#..#..##..##",
+        "#..######.###...####..#..#####..##..#.#####...##.#.#..#.##..#.#......#.###",
+        ".######.###.####...#.##.##..#..#..#####.....#.#....###..#.##......#.....#.",
+        ".#..#..##..#...##.######.####.####.#.#...#.......#..#.#.#...####.##.#.....",
+        ".#..#...##.#.##..#...##.#.##..###.#......#.#.......#.#.#.####.###.##...#..",
+        "...####.#..#..#.##.#....##..#.####....##...##..#...#......#.#.......#.....",
+        "..##..####..#...#.#.#...##..#.#..###..#####........#..####......#..#\n",
+        "\n",
+        "#..#.\n",
+        "#....\n",
+        "##..#\n",
+        "..#..\n",
+        "..###\n",
+    ));
+    assert_eq!(alg.len(), 512);
     for i in 1..=50 {
-        image = enhance(&alg, &image);
-        print!(" {}", i);
+        image = image.enhance(&alg);
+        match i {
+            2 => assert_eq!(image.count_lit(), Some(35)),
+            50 => assert_eq!(image.count_lit(), Some(3351)),
+            _ => (),
+        }
     }
-    println!();
+}
 
-    // draw(&image);
-    let mut count = 0;
-    for x in 10..(image.width() - 10) as isize {
-        for y in 10..(image.width() - 10) as isize {
-            if image[(x, y)] {
-                count += 1;
-            }
-        }
+#[test]
+fn test_flipping_background() {
+    // Dark neighborhoods light up, lit ones go dark.
+    let mut alg = vec![false; 512];
+    alg[0] = true;
+    let (_, mut image) = parse_input("#\n\n#.\n.#\n");
+    assert_eq!(image.count_lit(), Some(2));
+    for i in 1..=6 {
+        image = image.enhance(&alg);
+        assert_eq!(image.background, i % 2 == 1);
+        assert_eq!(image.count_lit().is_some(), i % 2 == 0);
     }
-    println!("Grand total: {}", count);
 }
@@ -1,3 +1,6 @@
+use lib::render::{FrameWriter, Rgb};
+use lib::Vec2D;
+
 type Ocean = Vec<Vec<Tile>>;
 #[derive(PartialEq, Clone, Copy)]
 enum Tile {
@@ -37,6 +40,14 @@ fn step(ocean: &mut Ocean, dir: (usize, usize), obj: Tile) -> bool {
     true
 }
 
+fn tile_colour(tile: &Tile) -> Rgb {
+    match tile {
+        Tile::East => (255, 128, 0),
+        Tile::South => (0, 160, 255),
+        Tile::Nothing => (0, 0, 32),
+    }
+}
+
 fn main() {
     let mut ocean: Vec<Vec<Tile>> = vec![];
     for line in std::fs::read_to_string("../inputs/25.txt")
@@ -58,9 +69,17 @@ fn main() {
         ocean.push(out_line);
     }
 
+    let mut frames = FrameWriter::from_args("day25");
+    if let Some(frames) = &mut frames {
+        frames.ppm(&Vec2D::from_rows(&ocean), &tile_colour).unwrap();
+    }
+
     for part1 in 1.. {
         let east = step(&mut ocean, (1, 0), Tile::East);
         let south = step(&mut ocean, (0, 1), Tile::South);
+        if let Some(frames) = &mut frames {
+            frames.ppm(&Vec2D::from_rows(&ocean), &tile_colour).unwrap();
+        }
         // Remember you can't do: if step(east) || step (north)
         // because || is short-circuiting: you will miss north steps.
         if !(east || south) {
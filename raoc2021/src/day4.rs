@@ -1,159 +1,285 @@
+use ansi_term::Colour;
+use lib::term::Painter;
 use lib::*;
+use std::collections::HashMap;
 
-fn a() -> (u32, u32) {
-    let mut lines = read_lines("../inputs/4.txt").unwrap();
-    let mut first: Option<u32> = None;
-    let mut last: u32 = 0;
+#[derive(Copy, Clone, Debug)]
+struct Cell {
+    number: u32,
+    marked: bool,
+}
 
-    // Read numbers
-    let numbers: Vec<u32> = lines
-        .next()
-        .expect("no line?")
-        .expect("why?")
-        .split(',')
-        .map(|n| n.parse::<u32>().expect("no parse?"))
-        .collect();
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum ParseError {
+    NotANumber(String),
+    /// Boards are square: each row must have as many numbers as
+    /// there are rows.
+    BadRow {
+        row: usize,
+        expected: usize,
+        found: usize,
+    },
+    /// A number appears twice on the same board.
+    Duplicate(u32),
+}
 
-    // Read grids
-    let mut grids: Vec<Grid> = Vec::new();
+/// An N×N bingo board.
+#[derive(Clone, Debug)]
+struct Board {
+    size: usize,
+    cells: Vec<Cell>,
+    /// Where each number is, so that marking is O(1).
+    index: HashMap<u32, usize>,
+    /// Whether diagonals count.
+    diagonals: bool,
+    /// How many cells are marked on each row, each column and each
+    /// diagonal, so that checking for a win is O(1) too.
+    marked_rows: Vec<usize>,
+    marked_cols: Vec<usize>,
+    marked_diags: [usize; 2],
+    won: bool,
+}
 
-    let mut raw: String = String::new();
+impl Board {
+    /// Parse a board from its rows.  Its size is its number of rows.
+    fn parse(rows: &[String], diagonals: bool) -> Result<Board, ParseError> {
+        let size = rows.len();
+        let mut cells = Vec::with_capacity(size * size);
+        let mut index = HashMap::new();
 
-    for l in lines.skip(1) {
-        if l.as_ref().unwrap().is_empty() {
-            grids.push(grid_parse(raw));
-            raw = String::new();
-        } else {
-            raw.push_str(l.unwrap().as_str());
-            raw.push(' ');
+        for (row, line) in rows.iter().enumerate() {
+            let mut found = 0;
+            for number in line.split(' ').filter(|n| !n.is_empty()) {
+                let number = number
+                    .parse::<u32>()
+                    .map_err(|_| ParseError::NotANumber(number.to_string()))?;
+                if index.insert(number, cells.len()).is_some() {
+                    return Err(ParseError::Duplicate(number));
+                }
+                cells.push(Cell {
+                    number,
+                    marked: false,
+                });
+                found += 1;
+            }
+            if found != size {
+                return Err(ParseError::BadRow {
+                    row,
+                    expected: size,
+                    found,
+                });
+            }
         }
+
+        Ok(Board {
+            size,
+            cells,
+            index,
+            diagonals,
+            marked_rows: vec![0; size],
+            marked_cols: vec![0; size],
+            marked_diags: [0, 0],
+            won: false,
+        })
     }
 
-    println!("Got {} grids", grids.len());
+    /// Mark a number.  Return true if this makes the board win.
+    fn mark(&mut self, n: u32) -> bool {
+        let Some(&i) = self.index.get(&n) else {
+            return false;
+        };
+        if self.cells[i].marked {
+            return false;
+        }
+        self.cells[i].marked = true;
 
-    for num in numbers {
-        println!("Drawn {}", num);
-        grids.retain(|grid| !grid_winning(&grid));
+        let (row, col) = (i / self.size, i % self.size);
+        self.marked_rows[row] += 1;
+        self.marked_cols[col] += 1;
+        if self.diagonals && row == col {
+            self.marked_diags[0] += 1;
+        }
+        if self.diagonals && row + col == self.size - 1 {
+            self.marked_diags[1] += 1;
+        }
 
-        for mut g in &mut grids {
-            grid_mark(&mut *g, num);
-            if let Some(score) = grid_maybe_score(&g) {
-                grid_print(&g);
-                if first.is_none() {
-                    first = Some(score * num)
-                }
-                last = score * num
+        let wins = self.marked_rows[row] == self.size
+            || self.marked_cols[col] == self.size
+            || self.marked_diags.contains(&self.size);
+        let first_win = wins && !self.won;
+        self.won |= wins;
+        first_win
+    }
+
+    /// The sum of unmarked numbers.
+    fn unmarked_sum(&self) -> u32 {
+        self.cells
+            .iter()
+            .filter(|c| !c.marked)
+            .map(|c| c.number)
+            .sum()
+    }
+
+    /// Print a board.  Marked cells are bracketed, or highlighted if
+    /// we're allowed to use colours.
+    fn print(&self, painter: &Painter) {
+        for (i, cell) in self.cells.iter().enumerate() {
+            if i % self.size == 0 {
+                println!();
+            }
+
+            let number = format!("{:02}", cell.number);
+            if cell.marked && painter.enabled() {
+                print!(
+                    " {}  ",
+                    painter.paint(Colour::Green.bold().reverse(), number)
+                )
+            } else if cell.marked {
+                print!("[{}] ", number)
+            } else {
+                print!(" {}  ", number)
             }
         }
+        println!();
     }
-    (first.unwrap(), last)
 }
 
-#[derive(Copy, Clone, Debug)]
-struct Cell {
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct Win {
+    /// The index of the winning board.
+    board: usize,
+    /// The number that made it win.
     number: u32,
-    marked: bool,
+    score: u32,
 }
 
-type Grid = [Cell; 25];
-
-fn grid_maybe_score(g: &Grid) -> Option<u32> {
-    if grid_winning(g) {
-        Some(grid_score(g))
-    } else {
-        None
+/// Draw every number, and return the boards in the order they won.
+fn play(boards: &mut [Board], numbers: &[u32]) -> Vec<Win> {
+    let mut ret = vec![];
+    for number in numbers {
+        for (i, board) in boards.iter_mut().enumerate() {
+            if !board.won && board.mark(*number) {
+                ret.push(Win {
+                    board: i,
+                    number: *number,
+                    score: board.unmarked_sum() * number,
+                });
+            }
+        }
     }
+    ret
 }
 
-/// Compute a grid score
-fn grid_score(g: &Grid) -> u32 {
-    fn fold(sum: u32, cell: &Cell) -> u32 {
-        if cell.marked {
-            sum
+fn read_input(diagonals: bool) -> (Vec<u32>, Vec<Board>) {
+    let mut lines = read_lines("../inputs/4.txt").unwrap().map(|l| l.unwrap());
+
+    // Read numbers
+    let numbers: Vec<u32> = lines
+        .next()
+        .expect("no line?")
+        .split(',')
+        .map(|n| n.parse::<u32>().expect("no parse?"))
+        .collect();
+
+    // Read boards
+    let mut boards: Vec<Board> = Vec::new();
+    let mut rows: Vec<String> = vec![];
+    for line in lines.skip(1).chain([String::new()]) {
+        if line.is_empty() {
+            if !rows.is_empty() {
+                let board = Board::parse(&rows, diagonals)
+                    .unwrap_or_else(|e| panic!("Bad board {}: {:?}", boards.len(), e));
+                boards.push(board);
+            }
+            rows.clear();
         } else {
-            sum + cell.number
+            rows.push(line);
         }
     }
-    g.iter().fold(0, fold)
+    (numbers, boards)
 }
 
-/// Determine if a grid is winning
-fn grid_winning(g: &Grid) -> bool {
-    // Check for colums
-    for i in 0..4 {
-        if g[i].marked
-            && g[i + 5].marked
-            && g[i + 10].marked
-            && g[i + 15].marked
-            && g[i + 20].marked
-        {
-            return true;
-        }
+fn main() {
+    // With --diagonals, diagonals win too.
+    let diagonals = std::env::args().any(|a| a == "--diagonals");
+    let painter = Painter::from_args();
+    let (numbers, mut boards) = read_input(diagonals);
+    println!("Got {} boards", boards.len());
+
+    let wins = play(&mut boards, &numbers);
+    for win in &wins {
+        println!(
+            "Board {} wins on {} with score {}",
+            win.board, win.number, win.score
+        );
     }
-    // Check for rows
-    for i in [0, 5, 10, 15, 20] {
-        if g[i].marked && g[i + 1].marked && g[i + 2].marked && g[i + 3].marked && g[i + 4].marked {
-            return true;
-        }
+    if let (Some(first), Some(last)) = (wins.first(), wins.last()) {
+        boards[first.board].print(&painter);
+        println!("Part 1: {}", first.score);
+        println!("Part 2: {}", last.score);
     }
-    false
 }
 
-fn grid_print(g: &Grid) {
-    for i in 0..25 {
-        if i % 5 == 0 {
-            println!();
-        }
-
-        if g[i].marked {
-            print!("[{:02}] ", g[i].number)
-        } else {
-            print!(" {:02}  ", g[i].number)
-        }
-    }
-    print!("\n");
+#[cfg(test)]
+fn parse_boards(raw: &str, diagonals: bool) -> Vec<Board> {
+    raw.split("\n\n")
+        .map(|b| {
+            let rows: Vec<String> = b.lines().map(String::from).collect();
+            Board::parse(&rows, diagonals).unwrap()
+        })
+        .collect()
 }
 
-/// Mark a grid
-fn grid_mark(g: &mut Grid, n: u32) {
-    for i in 0..g.len() {
-        if g[i].number == n {
-            g[i].marked = true;
-        }
-    }
+#[test]
+fn test_play() {
+    let numbers = [
+        7, 4, 9, 5, 11, 17, 23, 2, 0, 14, 21, 24, 10, 16, 13, 6, 15, 25, 12, 22, 18, 20, 8, 19, 3,
+        26, 1,
+    ];
+    let mut boards = parse_boards(
+        "22 13 17 11  0\n 8  2 23  4 24\n21  9 14 16  7\n 6 10  3 18  5\n 1 12 20 15 19\n\n\
+         \x20 3 15  0  2 22\n 9 18 13 17  5\n19  8  7 25 23\n20 11 10 24  4\n14 21 16 12  6\n\n\
+         14 21 17 24  4\n10 16 15  9 19\n18  8 23 26 20\n22 11 13  6  5\n 2  0 12  3  7",
+        false,
+    );
+    let wins = play(&mut boards, &numbers);
+    assert_eq!(
+        wins.iter().map(|w| w.board).collect::<Vec<_>>(),
+        vec![2, 0, 1]
+    );
+    assert_eq!(wins[0].score, 4512);
+    assert_eq!(wins[2].score, 1924);
 }
 
-/// Parse a grid
-fn grid_parse(raw: String) -> Grid {
-    let mut ret: Grid = [Cell {
-        number: 0,
-        marked: false,
-    }; 25];
-    let mut i: usize = 0;
-
-    for number in raw
-        .split(' ')
-        .filter(|l| !l.is_empty())
-        .map(|number| number.parse::<u32>().expect("no parse"))
-    {
-        println!("Got {}", number);
-        ret[i] = Cell {
-            number,
-            marked: false,
-        };
-        i += 1;
-        if i == 25 {
-            break;
-        };
-    }
-    println!("Parsed {}, Got:", raw);
-    grid_print(&ret);
+#[test]
+fn test_last_column_and_diagonals() {
+    let raw = "1 2 3\n4 5 6\n7 8 9";
+    let mut boards = parse_boards(raw, false);
+    assert_eq!(play(&mut boards, &[3, 6, 9])[0].number, 9);
 
-    ret
+    let mut boards = parse_boards(raw, false);
+    assert!(play(&mut boards, &[3, 5, 7]).is_empty());
+    let mut boards = parse_boards(raw, true);
+    assert_eq!(play(&mut boards, &[3, 5, 7])[0].score, 7 * 30);
 }
 
-fn main() {
-    let result = a();
-    println!("Part 1: {}", result.0);
-    println!("Part 2: {}", result.1);
+#[test]
+fn test_parse_errors() {
+    let rows = |raw: &str| raw.lines().map(String::from).collect::<Vec<_>>();
+    assert_eq!(
+        Board::parse(&rows("1 2\n3 4 5"), false).unwrap_err(),
+        ParseError::BadRow {
+            row: 1,
+            expected: 2,
+            found: 3
+        }
+    );
+    assert_eq!(
+        Board::parse(&rows("1 2\n3 1"), false).unwrap_err(),
+        ParseError::Duplicate(1)
+    );
+    assert_eq!(
+        Board::parse(&rows("1 x\n3 4"), false).unwrap_err(),
+        ParseError::NotANumber(String::from("x"))
+    );
 }
@@ -1,82 +1,148 @@
+use lib::matrix;
 use std::fs;
 
-fn make_state(raw: &[u64]) -> Vec<u64> {
-    let mut ret = vec![0, 0, 0, 0, 0, 0, 0, 0, 0];
-    for fish in raw {
-        ret[*fish as usize] += 1;
-    }
-    ret
+/// A school of lanternfish, as the number of fish for each timer
+/// value.  A fish whose timer reaches 0 resets to `reset` and spawns
+/// a new fish with timer `newborn`.
+#[derive(Clone, Debug)]
+struct FishSchool {
+    reset: usize,
+    newborn: usize,
+    counts: Vec<u128>,
 }
 
-fn part_b(days: u64) -> u64 {
-    // The interesting part about part 2 is that it's exactly the same
-    // as A, but you can't bruteforce your way to a result --- you're
-    // supposed to compute 256 days, not 80.
-
-    // let raw = String::from("3,4,3,1,2");
-    let raw = fs::read_to_string("../inputs/6.txt").unwrap();
-
-    let mut state = make_state(
-        &raw.split(",")
-            .map(|x| x.trim().parse::<u64>().unwrap())
-            .collect::<Vec<u64>>(),
-    );
-
-    for _ in 1..=days {
-        let zeros = state[0];
-        for i in 1..9 {
-            // println!("Working at {}", i);
-            state[i - 1] = state[i];
+impl FishSchool {
+    fn new(timers: &[usize], reset: usize, newborn: usize) -> FishSchool {
+        let max = timers
+            .iter()
+            .copied()
+            .chain([reset, newborn])
+            .max()
+            .unwrap();
+        let mut counts = vec![0; max + 1];
+        for fish in timers {
+            counts[*fish] += 1;
         }
-        // zeros have become 6s, and have made 8s
-        state[6] += zeros;
-        state[8] = zeros;
+        FishSchool {
+            reset,
+            newborn,
+            counts,
+        }
+    }
 
-        // println!("Method B day {}, state: {:?}, count {}", day, state,
-        //      state.iter().sum::<u64>());
+    /// The matrix of a day: `matrix[i][j]` is how many fish with
+    /// timer j a fish with timer i becomes.
+    fn transition(&self) -> matrix::Matrix {
+        let mut ret = vec![vec![0; self.counts.len()]; self.counts.len()];
+        for (timer, row) in ret.iter_mut().enumerate().skip(1) {
+            row[timer - 1] = 1;
+        }
+        ret[0][self.reset] += 1;
+        ret[0][self.newborn] += 1;
+        ret
     }
 
-    state.iter().sum()
+    /// The number of fish after `days`, exact or modulo `modulus` as
+    /// described in [`lib::matrix`].
+    fn after(&self, days: u64, modulus: Option<u64>) -> u128 {
+        let counts = matrix::vec_mul_pow(&self.counts, &self.transition(), days, modulus);
+        match modulus {
+            Some(m) => counts.iter().fold(0, |acc, c| (acc + c) % m as u128),
+            None => counts.iter().sum(),
+        }
+    }
 }
 
-fn part_a(days: u64) -> u64 {
-    let raw = fs::read_to_string("../inputs/6.txt").unwrap();
+fn read_input() -> Vec<usize> {
     // let raw = String::from("3,4,3,1,2");
+    let raw = fs::read_to_string("../inputs/6.txt").unwrap();
 
-    let mut state: Vec<u64> = raw
-        .split(",")
-        .map(|x| x.trim().parse::<u64>().unwrap())
-        .collect();
+    raw.split(',')
+        .map(|x| x.trim().parse::<usize>().unwrap())
+        .collect()
+}
+
+/// The reference implementation: materialize every single fish.
+fn part_a(timers: &[usize], reset: usize, newborn: usize, days: u64) -> u64 {
+    let mut state: Vec<usize> = timers.to_vec();
 
-    fn evolve(s: u64) -> u64 {
+    let evolve = |s: usize| {
         if s > 0 {
             s - 1
         } else {
-            6
+            reset
         }
-    }
+    };
 
     for _ in 1..=days {
-        let children = state.clone().into_iter().filter(|x| *x == 0).count();
-        state = state.into_iter().map(&evolve).collect();
-        for _ in 0..children {
-            state.push(8);
-        }
-        // println!("After {} days: {:?}, count {}", i,
-        //          make_state(&state), state.len());
+        let children = state.iter().filter(|x| **x == 0).count();
+        state = state.into_iter().map(evolve).collect();
+        state.extend(std::iter::repeat_n(newborn, children));
     }
     state.len() as u64
 }
 
 fn main() {
+    let timers = read_input();
+    let school = FishSchool::new(&timers, 6, 8);
+
     println!("Part 1 (method A)…");
-    println!(" = {}", part_a(80));
+    println!(" = {}", part_a(&timers, 6, 8, 80));
     println!("Part 1 (method B)…");
-    println!(" = {}", part_b(80));
+    println!(" = {}", school.after(80, None));
     println!("Part 2 (method B)…");
-    println!(" = {}", part_b(256));
-    // println!("Part 2 (method B): {}", part_b(256));
-    // let mut v = vec![1,2,3];
-    // v = v.into_iter().map(|x| x+1).collect();
-    // println!("{:?}", v);
+    println!(" = {}", school.after(256, None));
+
+    // --days N counts fish after N days, modulo a large prime.
+    let args: Vec<String> = std::env::args().collect();
+    for pair in args.windows(2) {
+        if pair[0] == "--days" {
+            let days: u64 = pair[1].parse().expect("--days wants a number");
+            println!(
+                "After {} days, modulo 1000000007: {}",
+                days,
+                school.after(days, Some(1_000_000_007))
+            );
+        }
+    }
+}
+
+#[test]
+fn test_fish_school() {
+    let school = FishSchool::new(&[3, 4, 3, 1, 2], 6, 8);
+    assert_eq!(school.after(18, None), 26);
+    assert_eq!(school.after(80, None), 5934);
+    assert_eq!(school.after(256, None), 26984457539);
+    assert_eq!(
+        school.after(256, Some(1_000_000_007)),
+        26984457539 % 1_000_000_007
+    );
+    // Doesn't take forever.
+    school.after(1_000_000_000_000_000_000, Some(1_000_000_007));
+}
+
+#[test]
+fn test_against_reference() {
+    // A small linear congruential generator, to get varied but
+    // reproducible schools.
+    let mut seed: u64 = 42;
+    let mut rand = |n: u64| {
+        seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1);
+        (seed >> 33) % n
+    };
+    for _ in 0..50 {
+        let reset = rand(8) as usize;
+        let newborn = rand(10) as usize;
+        let timers: Vec<usize> = (0..rand(6) + 1).map(|_| rand(9) as usize).collect();
+        let days = rand(40);
+        assert_eq!(
+            FishSchool::new(&timers, reset, newborn).after(days, None),
+            part_a(&timers, reset, newborn, days) as u128,
+            "timers={:?} reset={} newborn={} days={}",
+            timers,
+            reset,
+            newborn,
+            days
+        );
+    }
 }
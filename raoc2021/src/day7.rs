@@ -1,56 +1,123 @@
-use std::cmp::{max, min};
 use std::fs;
 
-fn distance(a: u32, b: u32) -> u32 {
-    max(a, b) - min(a, b)
+/// Where the crabs align, and how much fuel it takes them.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct Alignment {
+    position: i64,
+    fuel: u64,
 }
 
-fn cost_b(a: u32, b: u32) -> u32 {
-    let dist = distance(a, b) as f64;
+fn distance(a: i64, b: i64) -> u64 {
+    a.abs_diff(b)
+}
 
-    let ret = (dist.powf(2.0) / 2.0) + (dist / 2.0);
-    ret as u32
+/// Each step costs one more than the previous one, so moving n
+/// steps costs the nth triangular number.
+fn cost_b(a: i64, b: i64) -> u64 {
+    let dist = distance(a, b);
+    dist * (dist + 1) / 2
 }
 
-fn part_a() -> (u32, u32) {
-    // let raw = String::from("16,1,2,0,4,2,7,1,2,14");
-    let raw = fs::read_to_string("../inputs/7.txt").unwrap();
-    let positions: Vec<u32> = raw
-        .trim()
-        .split(",")
-        .map(|x| x.parse::<u32>().unwrap())
-        .collect();
-
-    let min = positions.iter().min().unwrap();
-    let max = positions.iter().max().unwrap();
-
-    let mut best_a: Option<u32> = None;
-    let mut best_b: Option<u32> = None;
-    for cand in *min..=*max {
-        // let mut cost = 0;
-        let cost_a: u32 = positions.iter().map(|x| distance(*x, cand)).sum();
-        let cost_b: u32 = positions.iter().map(|x| cost_b(*x, cand)).sum();
-        if let Some(x) = best_a {
-            if x > cost_a {
-                best_a = Some(cost_a)
-            }
-        } else {
-            best_a = Some(cost_a)
-        }
+fn align_at(positions: &[i64], target: i64, cost: &dyn Fn(i64, i64) -> u64) -> Alignment {
+    Alignment {
+        position: target,
+        fuel: positions.iter().map(|x| cost(*x, target)).sum(),
+    }
+}
 
-        if let Some(x) = best_b {
-            if x > cost_b {
-                best_b = Some(cost_b)
+/// With a linear cost, any median is optimal: moving away from it
+/// brings the target closer to at most half of the crabs.
+fn align_linear(positions: &[i64]) -> Alignment {
+    let mut sorted = positions.to_vec();
+    sorted.sort_unstable();
+    align_at(positions, sorted[sorted.len() / 2], &distance)
+}
+
+/// With a triangular cost, the optimum is within 1/2 of the mean, so
+/// we only have to try the integers around it.
+fn align_triangular(positions: &[i64]) -> Alignment {
+    let n = positions.len() as i64;
+    let floor = positions.iter().sum::<i64>().div_euclid(n);
+    [floor, floor + 1]
+        .iter()
+        .map(|target| align_at(positions, *target, &cost_b))
+        .min_by_key(|a| a.fuel)
+        .unwrap()
+}
+
+/// For any convex cost, the total cost is convex too, so we can
+/// ternary search the range of positions for its minimum.
+fn align_convex(positions: &[i64], cost: &dyn Fn(i64, i64) -> u64) -> Alignment {
+    let mut lo = *positions.iter().min().unwrap();
+    let mut hi = *positions.iter().max().unwrap();
+    let fuel = |target| align_at(positions, target, cost).fuel;
+    while hi - lo > 2 {
+        let m1 = lo + (hi - lo) / 3;
+        let m2 = hi - (hi - lo) / 3;
+        match fuel(m1).cmp(&fuel(m2)) {
+            std::cmp::Ordering::Less => hi = m2 - 1,
+            std::cmp::Ordering::Greater => lo = m1 + 1,
+            std::cmp::Ordering::Equal => {
+                lo = m1;
+                hi = m2;
             }
-        } else {
-            best_b = Some(cost_b)
         }
     }
+    (lo..=hi)
+        .map(|target| align_at(positions, target, cost))
+        .min_by_key(|a| a.fuel)
+        .unwrap()
+}
 
-    (best_a.unwrap(), best_b.unwrap())
+fn read_input() -> Vec<i64> {
+    // let raw = String::from("16,1,2,0,4,2,7,1,2,14");
+    let raw = fs::read_to_string("../inputs/7.txt").unwrap();
+    raw.trim()
+        .split(',')
+        .map(|x| x.parse::<i64>().unwrap())
+        .collect()
 }
 
 fn main() {
-    println!("Part A: {:?}", part_a());
-    // println!("Part B: {}", part_b());
+    let positions = read_input();
+    println!("Part A: {:?}", align_linear(&positions));
+    let part_b = align_triangular(&positions);
+    assert_eq!(part_b.fuel, align_convex(&positions, &cost_b).fuel);
+    println!("Part B: {:?}", part_b);
+}
+
+#[test]
+fn test_align() {
+    let positions = [16, 1, 2, 0, 4, 2, 7, 1, 2, 14];
+    let a = Alignment {
+        position: 2,
+        fuel: 37,
+    };
+    let b = Alignment {
+        position: 5,
+        fuel: 168,
+    };
+    assert_eq!(align_linear(&positions), a);
+    assert_eq!(align_convex(&positions, &distance), a);
+    assert_eq!(align_triangular(&positions), b);
+    assert_eq!(align_convex(&positions, &cost_b), b);
+}
+
+#[test]
+fn test_align_brute_force() {
+    let square = |a: i64, b: i64| distance(a, b).pow(2);
+    let inputs: [&[i64]; 4] = [&[3], &[-5, 5], &[1, 1, 1, 100], &[-7, 0, 3, 3, 12, -2, 9]];
+    for positions in inputs {
+        let lo = *positions.iter().min().unwrap();
+        let hi = *positions.iter().max().unwrap();
+        let best = |cost: &dyn Fn(i64, i64) -> u64| {
+            (lo..=hi)
+                .map(|t| align_at(positions, t, cost).fuel)
+                .min()
+                .unwrap()
+        };
+        assert_eq!(align_linear(positions).fuel, best(&distance));
+        assert_eq!(align_triangular(positions).fuel, best(&cost_b));
+        assert_eq!(align_convex(positions, &square).fuel, best(&square));
+    }
 }
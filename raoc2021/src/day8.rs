@@ -1,4 +1,5 @@
 use lib::*;
+use std::fmt;
 
 // Segments masks
 const MASK_A: u8 = 1;
@@ -21,167 +22,225 @@ const REPR_7: u8 = MASK_A | MASK_C | MASK_F;
 const REPR_8: u8 = MASK_A | MASK_B | MASK_C | MASK_D | MASK_E | MASK_F | MASK_G;
 const REPR_9: u8 = MASK_A | MASK_B | MASK_C | MASK_D | MASK_F | MASK_G;
 
-fn count_bits(n: &u8) -> u8 {
-    let mut ret = 0;
-    if 0 != n & 1 {
-        ret += 1
-    };
-    if 0 != n & 2 {
-        ret += 1
-    };
-    if 0 != n & 4 {
-        ret += 1
-    };
-    if 0 != n & 8 {
-        ret += 1
-    };
-    if 0 != n & 16 {
-        ret += 1
-    };
-    if 0 != n & 32 {
-        ret += 1
-    };
-    if 0 != n & 64 {
-        ret += 1
-    };
-    if 0 != n & 128 {
-        ret += 1
-    };
-    ret
+const DIGITS: [u8; 10] = [
+    REPR_0, REPR_1, REPR_2, REPR_3, REPR_4, REPR_5, REPR_6, REPR_7, REPR_8, REPR_9,
+];
+
+// Hexadecimal digits, as in A b C d E F.
+const REPR_HEX_A: u8 = MASK_A | MASK_B | MASK_C | MASK_D | MASK_E | MASK_F;
+const REPR_HEX_B: u8 = MASK_B | MASK_D | MASK_E | MASK_F | MASK_G;
+const REPR_HEX_C: u8 = MASK_A | MASK_B | MASK_E | MASK_G;
+const REPR_HEX_D: u8 = MASK_C | MASK_D | MASK_E | MASK_F | MASK_G;
+const REPR_HEX_E: u8 = MASK_A | MASK_B | MASK_D | MASK_E | MASK_G;
+const REPR_HEX_F: u8 = MASK_A | MASK_B | MASK_D | MASK_E;
+
+const HEX_DIGITS: [u8; 16] = [
+    REPR_0, REPR_1, REPR_2, REPR_3, REPR_4, REPR_5, REPR_6, REPR_7, REPR_8, REPR_9, REPR_HEX_A,
+    REPR_HEX_B, REPR_HEX_C, REPR_HEX_D, REPR_HEX_E, REPR_HEX_F,
+];
+
+/// A wiring, mapping each scrambled wire (by index) to the segment
+/// it actually lights up (by index).
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Wiring(Vec<usize>);
+
+impl Wiring {
+    /// Translate a scrambled pattern into real segments.
+    fn apply(&self, pattern: u8) -> u8 {
+        let mut ret = 0;
+        for (wire, segment) in self.0.iter().enumerate() {
+            if pattern & (1 << wire) != 0 {
+                ret |= 1 << segment;
+            }
+        }
+        ret
+    }
 }
 
-/// Read a number as a segment mask and return its value.
-fn read_segments(segments: u8) -> u8 {
-    match segments {
-        REPR_0 => 0,
-        REPR_1 => 1,
-        REPR_2 => 2,
-        REPR_3 => 3,
-        REPR_4 => 4,
-        REPR_5 => 5,
-        REPR_6 => 6,
-        REPR_7 => 7,
-        REPR_8 => 8,
-        REPR_9 => 9,
-        _ => panic!("Bad segment combination"),
+/// Prints as `a→c b→f…`, from scrambled wires to segments.
+impl fmt::Display for Wiring {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (wire, segment) in self.0.iter().enumerate() {
+            if wire > 0 {
+                write!(f, " ")?;
+            }
+            write!(
+                f,
+                "{}→{}",
+                (b'a' + wire as u8) as char,
+                (b'a' + *segment as u8) as char
+            )?;
+        }
+        Ok(())
     }
 }
 
-fn solve(patterns: &[u8], puzzle: &[u8]) -> u32 {
-    // First step. We use patterns to associate lines to segments.
-
-    // Seven variables mapping segment X (seg_X) to candidate LINES.
-    // Base value is b01111111 = 127 = all segments are equally likely.
-    let mut seg_a = 127;
-    let mut seg_b = 127;
-    let mut seg_c = 127;
-    let mut seg_d = 127;
-    let mut seg_e = 127;
-    let mut seg_f = 127;
-    let mut seg_g = 127;
-
-    let mut fives: Vec<u8> = vec![];
-    let mut sixes: Vec<u8> = vec![];
-
-    // Return a mask of bit differences in three bytes.
-    fn diff(a: &u8, b: &u8, c: &u8) -> u8 {
-        (a ^ b) | (a ^ c) | (b ^ c)
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum SolveError {
+    /// No wiring explains the observations.
+    Inconsistent,
+    /// More than one wiring does.
+    Ambiguous(Wiring, Wiring),
+    /// This pattern doesn't show any symbol, whatever the wiring.
+    Unreadable(u8),
+}
+
+/// A segment display, as the segment masks of the symbols it can
+/// show, indexed by their value.
+struct Display<'a> {
+    symbols: &'a [u8],
+    /// The number of segments.
+    width: usize,
+}
+
+impl<'a> Display<'a> {
+    fn new(symbols: &'a [u8]) -> Display<'a> {
+        let all = symbols.iter().fold(0, |acc, s| acc | s);
+        Display {
+            symbols,
+            width: 8 - all.leading_zeros() as usize,
+        }
     }
 
-    for item in patterns {
-        match count_bits(item) {
-            2 => {
-                // This is digit 1.
-                seg_c = seg_c & item;
-                seg_f = seg_f & item;
-            }
-            3 => {
-                // This is digit 7
-                seg_a = seg_a & item;
-                seg_c = seg_c & item;
-                seg_f = seg_f & item;
-            }
-            4 => {
-                // This is digit 4
-                seg_b = seg_b & item;
-                seg_c = seg_c & item;
-                seg_d = seg_d & item;
-                seg_f = seg_f & item;
-            }
-            5 => {
-                // Either 2, 3 or 5.
-                fives.push(*item);
-            }
-            6 => {
-                // Either 0, 6 or 9
-                sixes.push(*item);
+    /// Read a segment mask and return its value.
+    fn read(&self, segments: u8) -> Option<usize> {
+        self.symbols.iter().position(|s| *s == segments)
+    }
+
+    /// Find the wiring that explains the scrambled `observations`.
+    ///
+    /// We start from "every wire can be any segment", then, for each
+    /// observation, restrict its wires to the segments used by the
+    /// symbols of the same length, and the other wires to the
+    /// segments those symbols don't use.  Wires that end up with a
+    /// single candidate take it away from the others.  Whatever
+    /// remains undecided is solved by backtracking.
+    fn solve(&self, observations: &[u8]) -> Result<Wiring, SolveError> {
+        let full: u8 = ((1u16 << self.width) - 1) as u8;
+        let mut candidates = vec![full; self.width];
+
+        for obs in observations {
+            if obs & !full != 0 {
+                return Err(SolveError::Inconsistent);
             }
-            7 => {} // This is 8, we don't care.
-            _ => {
-                // Abnormal
-                panic!("Nope")
+            let fits = self
+                .symbols
+                .iter()
+                .filter(|s| s.count_ones() == obs.count_ones());
+            let on = fits.clone().fold(0, |acc, s| acc | s);
+            let off = fits.fold(0, |acc, s| acc | (!s & full));
+            for (wire, cands) in candidates.iter_mut().enumerate() {
+                *cands &= if obs & (1 << wire) != 0 { on } else { off };
             }
         }
-    }
 
-    let diff5 = diff(&fives[0], &fives[1], &fives[2]);
-    seg_b = seg_b & diff5;
-    seg_e = seg_e & diff5;
-    seg_f = seg_f & diff5;
-
-    let inter5 = fives[0] & fives[1] & fives[2];
-    seg_a = seg_a & inter5;
-    seg_d = seg_d & inter5;
-    seg_g = seg_g & inter5;
-
-    let diff6 = diff(&sixes[0], &sixes[1], &sixes[2]);
-    seg_c = seg_c & diff6;
-    seg_d = seg_d & diff6;
-    seg_e = seg_e & diff6;
-
-    // Finally…  Not too much reasoning there: just ran the code in a
-    // loop, looked for intersections between solved segments and
-    // unsolved ones, and applied substractions.
-    seg_e = seg_e & !seg_c;
-    seg_f = seg_f & !seg_c;
-    seg_b = seg_b & !seg_f;
-    seg_b = seg_b & !seg_c;
-    seg_g = seg_g & !seg_a;
-    seg_g = seg_g & !seg_d;
-
-    // Part 2: solve the puzzle
-
-    let mut total: u32 = 0;
-    for piece in puzzle {
-        total *= 10;
-        let mut pattern = 0;
-        if piece & seg_a != 0 {
-            pattern |= MASK_A
-        }
-        if piece & seg_b != 0 {
-            pattern |= MASK_B
-        }
-        if piece & seg_c != 0 {
-            pattern |= MASK_C
+        let mut again = true;
+        while again {
+            again = false;
+            for wire in 0..self.width {
+                if candidates[wire].count_ones() != 1 {
+                    continue;
+                }
+                let taken = candidates[wire];
+                for (other, cands) in candidates.iter_mut().enumerate() {
+                    if other != wire && *cands & taken != 0 {
+                        *cands &= !taken;
+                        again = true;
+                    }
+                }
+            }
         }
-        if piece & seg_d != 0 {
-            pattern |= MASK_D
+
+        let mut found = vec![];
+        self.search(observations, &candidates, &mut vec![], 0, &mut found);
+        match found.len() {
+            0 => Err(SolveError::Inconsistent),
+            1 => Ok(found.pop().unwrap()),
+            _ => Err(SolveError::Ambiguous(found.remove(0), found.remove(0))),
         }
-        if piece & seg_e != 0 {
-            pattern |= MASK_E
+    }
+
+    /// Complete `wiring` in every possible way, and push those that
+    /// explain all `observations` to `found`.  Stop at two.
+    fn search(
+        &self,
+        observations: &[u8],
+        candidates: &[u8],
+        wiring: &mut Vec<usize>,
+        used: u8,
+        found: &mut Vec<Wiring>,
+    ) {
+        if found.len() > 1 {
+            return;
         }
-        if piece & seg_f != 0 {
-            pattern |= MASK_F
+        let wire = wiring.len();
+        if wire == self.width {
+            let wiring = Wiring(wiring.clone());
+            if observations
+                .iter()
+                .all(|obs| self.read(wiring.apply(*obs)).is_some())
+            {
+                found.push(wiring);
+            }
+            return;
         }
-        if piece & seg_g != 0 {
-            pattern |= MASK_G
+        for segment in 0..self.width {
+            let mask = 1 << segment;
+            if candidates[wire] & mask != 0 && used & mask == 0 {
+                wiring.push(segment);
+                self.search(observations, candidates, wiring, used | mask, found);
+                wiring.pop();
+            }
         }
+    }
+
+    /// Read symbols from scrambled patterns.
+    fn decode(&self, wiring: &Wiring, patterns: &[u8]) -> Result<Vec<usize>, SolveError> {
+        patterns
+            .iter()
+            .map(|pattern| {
+                self.read(wiring.apply(*pattern))
+                    .ok_or(SolveError::Unreadable(*pattern))
+            })
+            .collect()
+    }
+
+    /// The number written with `symbols`, most significant first.
+    fn value(&self, symbols: &[usize]) -> u64 {
+        symbols
+            .iter()
+            .fold(0, |acc, s| acc * self.symbols.len() as u64 + *s as u64)
+    }
+}
+
+/// What we learned from a line of input.
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Reading {
+    wiring: Wiring,
+    /// The symbols on the display, as values.
+    digits: Vec<usize>,
+    value: u64,
+}
 
-        // print!("{}", )
-        total += read_segments(pattern) as u32;
+impl Reading {
+    fn print(&self, display: &Display) {
+        println!("Wiring: {}", self.wiring);
+        println!("Value: {}", self.value);
+        for digit in &self.digits {
+            print_mask(display.symbols[*digit]);
+        }
     }
-    println!("{}", total);
-    total
+}
+
+fn solve(display: &Display, patterns: &[u8], puzzle: &[u8]) -> Result<Reading, SolveError> {
+    let wiring = display.solve(patterns)?;
+    let digits = display.decode(&wiring, puzzle)?;
+    let value = display.value(&digits);
+    Ok(Reading {
+        wiring,
+        digits,
+        value,
+    })
 }
 
 fn char_to_mask(c: char) -> u8 {
@@ -233,20 +292,86 @@ fn parse(s: &str) -> Vec<u8> {
 
 fn main() {
     let input = read_lines("../inputs/8.txt").unwrap();
+    let display = if std::env::args().any(|a| a == "--hex") {
+        Display::new(&HEX_DIGITS)
+    } else {
+        Display::new(&DIGITS)
+    };
+
+    // With --audit, print what we read on each line.
+    let audit = std::env::args().any(|a| a == "--audit");
 
     let mut count = 0;
     let mut sum = 0;
     for line in input {
-        let parts: Vec<String> = line.unwrap().split(" | ").map(String::from).collect();
+        let line = line.unwrap();
+        let (patterns, puzzle) = line.split_once(" | ").unwrap();
 
-        let patterns = parse(&parts[0]);
-        let puzzle = parse(&parts[1]);
-        sum += solve(&patterns, &puzzle);
-        count += parts[1]
-            .split(" ")
+        match solve(&display, &parse(patterns), &parse(puzzle)) {
+            Ok(reading) => {
+                if audit {
+                    println!("{}", line);
+                    reading.print(&display);
+                }
+                sum += reading.value
+            }
+            Err(err) => println!("Can't read {}: {:?}", line, err),
+        }
+        count += puzzle
+            .split(' ')
             .filter(|item| [2, 3, 4, 7].contains(&item.len()))
             .count();
     }
 
     println!("Part A: {}\nPart B: {}", count, sum)
 }
+
+#[test]
+fn test_solve() {
+    let display = Display::new(&DIGITS);
+    let patterns = parse("acedgfb cdfbe gcdfa fbcad dab cefabd cdfgeb eafb cagedb ab");
+    let puzzle = parse("cdfeb fcadb cdfeb cdbaf");
+    let reading = solve(&display, &patterns, &puzzle).unwrap();
+    assert_eq!(reading.value, 5353);
+    assert_eq!(reading.digits, vec![5, 3, 5, 3]);
+    assert_eq!(reading.wiring.to_string(), "a→c b→f c→g d→a e→b f→d g→e");
+    // Without 1, 4, 7 and 8 we can't tell c from f.
+    let patterns = parse("cdfbe gcdfa fbcad cefabd cdfgeb cagedb");
+    assert!(matches!(
+        display.solve(&patterns),
+        Err(SolveError::Ambiguous(_, _))
+    ));
+    // No digit has a single segment.
+    assert_eq!(display.solve(&parse("ab a")), Err(SolveError::Inconsistent));
+}
+
+#[test]
+fn test_solve_hex() {
+    for (i, a) in HEX_DIGITS.iter().enumerate() {
+        assert!(!HEX_DIGITS[i + 1..].contains(a), "{:x} is ambiguous", i);
+    }
+
+    let display = Display::new(&HEX_DIGITS);
+    let wiring = Wiring(vec![3, 5, 0, 6, 1, 2, 4]);
+    // Scramble every symbol through the inverse of the wiring.
+    let scramble = |symbol: u8| {
+        (0..7)
+            .filter(|wire| symbol & (1 << wiring.0[*wire]) != 0)
+            .fold(0, |acc, wire| acc | 1 << wire)
+    };
+    let observations: Vec<u8> = HEX_DIGITS.iter().map(|s| scramble(*s)).collect();
+    assert_eq!(display.solve(&observations), Ok(wiring.clone()));
+    let puzzle: Vec<u8> = [REPR_HEX_B, REPR_HEX_E, REPR_HEX_E, REPR_HEX_F]
+        .iter()
+        .map(|s| scramble(*s))
+        .collect();
+    let digits = display.decode(&wiring, &puzzle).unwrap();
+    assert_eq!(display.value(&digits), 0xbeef);
+    let puzzle: Vec<u8> = [REPR_HEX_A, REPR_0, REPR_HEX_C, REPR_HEX_D]
+        .iter()
+        .map(|s| scramble(*s))
+        .collect();
+    let digits = display.decode(&wiring, &puzzle).unwrap();
+    assert_eq!(digits, vec![0xa, 0, 0xc, 0xd]);
+    assert_eq!(display.value(&digits), 0xa0cd);
+}
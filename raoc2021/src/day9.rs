@@ -1,5 +1,6 @@
+use ansi_term::Colour;
+use lib::term::{heat, Painter};
 use lib::*;
-// use std::cmp::{max, min};
 use std::ops::{Index, IndexMut};
 
 struct Vec2D<T> {
@@ -13,10 +14,6 @@ impl<T> Vec2D<T> {
         y * self.width + x
     }
 
-    fn to_coords(&self, idx: usize) -> (usize, usize) {
-        (idx % self.width, idx / self.width)
-    }
-
     fn test_coords(&self, x: isize, y: isize) -> bool {
         if x < 0 {
             return false;
@@ -45,8 +42,6 @@ impl<T: Copy> Vec2D<T> {
             vec.push(value);
         }
 
-        println!("Vec size is {}", vec.len());
-
         Vec2D { vec, width, height }
     }
 
@@ -74,23 +69,29 @@ impl<T> IndexMut<(usize, usize)> for Vec2D<T> {
     }
 }
 
+/// Basins are numbered from 0, in the order we first meet them
+/// scanning the map row by row.
+type BasinId = usize;
+
 impl Vec2D<u8> {
-    fn draw(&self) {
-        for i in 0..self.vec.capacity() {
+    /// Draw the map, coloured by basin size if the painter allows
+    /// it: the larger the basin, the hotter the colour.  `basins`
+    /// maps each cell to its basin, `sizes` maps basins to their size.
+    fn draw_basins(&self, basins: &Vec2D<Option<BasinId>>, sizes: &[usize], painter: &Painter) {
+        let max_size = sizes.iter().copied().max().unwrap_or(0) as u32;
+        for (i, val) in self.vec.iter().enumerate() {
             if i % self.width == 0 {
-                println!("");
+                println!();
+            }
+            match basins.vec[i] {
+                Some(basin) => print!(
+                    "{}",
+                    painter.paint(heat(sizes[basin] as u32, max_size), val)
+                ),
+                None => print!("{}", painter.paint(Colour::Fixed(240), val)),
             }
-            let val = self.vec[i];
-            print!(
-                "{}",
-                if val < 10 {
-                    val.to_string()
-                } else {
-                    String::from("X")
-                }
-            );
         }
-        println!("");
+        println!();
     }
 
     fn is_low_point(&self, x: usize, y: usize) -> bool {
@@ -103,10 +104,8 @@ impl Vec2D<u8> {
         let l = self.safe_index(x - 1, y);
         let r = self.safe_index(x + 1, y);
         let mut ret = true;
-        for side in &[u, d, l, r] {
-            if let Some(side) = side {
-                ret &= side > &val
-            }
+        for side in [u, d, l, r].iter().flatten() {
+            ret &= side > &val
         }
         ret
     }
@@ -128,86 +127,104 @@ fn read_number(c: char) -> u8 {
     }
 }
 
-fn explore_basin(map: &mut Vec2D<u8>, x: usize, y: usize, size: u32) -> u32 {
-    // To explore a basin, we recursively look for higher, but < 9,
-    // points around our starting point.  Because we don't want to
-    // explore the same point twice, we mark our location by or-ing it
-    // with 0b10000000.
-    let mark = 128;
-    let value = map[(x, y)];
-    let mut ret = size;
-
-    if value & mark != 0 {
-        return 0; // We're been there already.
-    } else {
-        ret += 1;
-        map[(x, y)] |= mark; // Mark our place.
-        println!(
-            "Point at {},{}, value is {}, size is now {}",
-            x, y, value, size
-        );
-        // map.draw();
+/// Find the root of the set `i` belongs to, flattening the path to
+/// it on the way.
+fn find(parent: &mut [usize], mut i: usize) -> usize {
+    let mut root = i;
+    while parent[root] != root {
+        root = parent[root];
+    }
+    while parent[i] != root {
+        let next = parent[i];
+        parent[i] = root;
+        i = next;
     }
+    root
+}
 
-    // Compute corners
-    let xi = x as isize;
-    let yi = y as isize;
+/// Label every cell of the map with the basin it belongs to.
+///
+/// A basin is every cell that isn't a 9 and can be reached from
+/// another cell of the basin without crossing a 9.  This doesn't
+/// care about low points at all, so flat areas and basins with more
+/// than one low point are handled the same as any other.  We merge
+/// each cell with its right and bottom neighbours in a union-find,
+/// then number the sets.
+///
+/// Returns the label of each cell (`None` for 9s), and the size of
+/// each basin.
+fn label_basins(map: &Vec2D<u8>) -> (Vec2D<Option<BasinId>>, Vec<usize>) {
+    let mut parent: Vec<usize> = (0..map.vec.len()).collect();
+    for y in 0..map.height {
+        for x in 0..map.width {
+            if map[(x, y)] == 9 {
+                continue;
+            }
+            let i = map.to_index((x, y));
+            for (x2, y2) in [(x + 1, y), (x, y + 1)] {
+                if x2 < map.width && y2 < map.height && map[(x2, y2)] != 9 {
+                    let a = find(&mut parent, i);
+                    let b = find(&mut parent, map.to_index((x2, y2)));
+                    parent[a] = b;
+                }
+            }
+        }
+    }
 
-    for step in [(-1, 0), (1, 0), (0, -1), (0, 1)] {
-        let x2 = xi + step.0;
-        let y2 = yi + step.1;
-        if !map.test_coords(x2, y2) {
+    let mut labels = Vec2D::new(map.width, map.height, None);
+    let mut id_of_root: Vec<Option<BasinId>> = vec![None; map.vec.len()];
+    let mut sizes = vec![];
+    for i in 0..map.vec.len() {
+        if map.vec[i] == 9 {
             continue;
         }
-        if let Some(next_value) = map.safe_index(x2, y2) {
-            if next_value < 9 && next_value > value {
-                ret += explore_basin(map, x2 as usize, y2 as usize, 0);
-            }
-        }
+        let root = find(&mut parent, i);
+        let id = *id_of_root[root].get_or_insert_with(|| {
+            sizes.push(0);
+            sizes.len() - 1
+        });
+        sizes[id] += 1;
+        labels.vec[i] = Some(id);
     }
-    ret
+    (labels, sizes)
 }
 
-fn main() {
-    let raw: Vec<String> = read_lines("../inputs/9.txt")
-        .unwrap()
-        .map(|l| l.unwrap())
-        .collect();
-
-    let height = raw.len();
-    let width = raw[1].len();
+/// Read a map, one row per line.
+fn parse_map(lines: &[String]) -> Vec2D<u8> {
+    let height = lines.len();
+    let width = lines[0].len();
 
     let mut map: Vec2D<u8> = Vec2D::new(width, height, 0);
 
     for y in 0..height {
         for x in 0..width {
-            map[(x, y)] = read_number(raw[y].chars().nth(x).unwrap());
+            map[(x, y)] = read_number(lines[y].chars().nth(x).unwrap());
         }
     }
+    map
+}
+
+fn main() {
+    let raw: Vec<String> = read_lines("../inputs/9.txt")
+        .unwrap()
+        .map(|l| l.unwrap())
+        .collect();
+
+    let map = parse_map(&raw);
+    let (height, width) = (map.height, map.width);
 
-    let mut low_points: Vec<(usize, usize)> = vec![];
     let mut total: u32 = 0;
     for y in 0..height {
         for x in 0..width {
             if map.is_low_point(x, y) {
-                low_points.push((x, y));
                 total += 1;
                 total += map[(x, y)] as u32;
             }
         }
     }
 
-    let mut basins: Vec<u32> = vec![];
-    for b in low_points {
-        println!("Exploring: {:?}", b);
-        basins.push(explore_basin(&mut map, b.0, b.1, 0));
-
-        println!("Cleaning…");
-        for i in 0..map.vec.len() {
-            map.vec[i] &= 0b01111111
-        }
-        map.draw();
-    }
+    let (labels, mut basins) = label_basins(&map);
+    map.draw_basins(&labels, &basins, &Painter::from_args());
 
     println!("Part 1: {}", total);
 
@@ -217,6 +234,36 @@ fn main() {
         "Part 2: {:?}",
         basins.pop().unwrap() * basins.pop().unwrap() * basins.pop().unwrap()
     );
+}
+
+#[test]
+fn test_label_basins() {
+    let map = |rows: &[&str]| parse_map(&rows.iter().map(|r| r.to_string()).collect::<Vec<_>>());
+
+    let example = map(&[
+        "2199943210",
+        "3987894921",
+        "9856789892",
+        "8767896789",
+        "9899965678",
+    ]);
+    let (labels, sizes) = label_basins(&example);
+    assert_eq!(sizes, vec![3, 9, 14, 9]);
+    assert_eq!(labels[(0, 0)], Some(0));
+    assert_eq!(labels[(9, 0)], Some(1));
+    assert_eq!(labels[(2, 0)], None);
+
+    // Flat basins have no low point at all: only (1, 2) is one here.
+    let (labels, sizes) = label_basins(&map(&["11191", "99921", "21292"]));
+    assert_eq!(sizes, vec![3, 4, 3]);
+    assert_eq!(labels[(4, 0)], labels[(3, 1)]);
+    assert_eq!(labels[(3, 2)], None);
 
-    // To find basins, we iterate over the list of low points.
+    // Both 1s are low points, in the same basin.
+    let two_lows = map(&["121", "999"]);
+    assert!(two_lows.is_low_point(0, 0) && two_lows.is_low_point(2, 0));
+    let (labels, sizes) = label_basins(&two_lows);
+    assert_eq!(sizes, vec![3]);
+    assert_eq!(labels[(0, 0)], Some(0));
+    assert_eq!(labels[(2, 0)], Some(0));
 }
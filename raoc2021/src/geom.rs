@@ -0,0 +1,232 @@
+//! Integer geometry on the plane.
+
+use std::cmp::Ordering;
+use std::num::ParseIntError;
+use std::str::FromStr;
+
+pub type Point = (i64, i64);
+
+/// A line segment between two integer points, both included.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Segment {
+    pub start: Point,
+    pub end: Point,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ParseError {
+    /// Segments look like `x1,y1 -> x2,y2`.
+    BadFormat(String),
+    NotANumber(ParseIntError),
+}
+
+impl From<ParseIntError> for ParseError {
+    fn from(e: ParseIntError) -> ParseError {
+        ParseError::NotANumber(e)
+    }
+}
+
+/// Parse a point of the form `12,29`.
+fn parse_point(s: &str) -> Result<Point, ParseError> {
+    let (x, y) = s
+        .split_once(',')
+        .ok_or_else(|| ParseError::BadFormat(s.to_string()))?;
+    Ok((x.trim().parse()?, y.trim().parse()?))
+}
+
+/// Parse a segment description of the form
+/// 12,29 -> 28,12
+impl FromStr for Segment {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (start, end) = s
+            .split_once("->")
+            .ok_or_else(|| ParseError::BadFormat(s.to_string()))?;
+        Ok(Segment {
+            start: parse_point(start)?,
+            end: parse_point(end)?,
+        })
+    }
+}
+
+/// Which side of the line through `a` and `b` the point `c` is on:
+/// `Greater` for counterclockwise, `Less` for clockwise, `Equal` if
+/// the three points are aligned.
+fn orientation(a: Point, b: Point, c: Point) -> Ordering {
+    let cross =
+        (b.0 - a.0) as i128 * (c.1 - a.1) as i128 - (b.1 - a.1) as i128 * (c.0 - a.0) as i128;
+    cross.cmp(&0)
+}
+
+impl Segment {
+    pub fn new(start: Point, end: Point) -> Segment {
+        Segment { start, end }
+    }
+
+    pub fn is_horizontal(&self) -> bool {
+        self.start.1 == self.end.1
+    }
+
+    pub fn is_vertical(&self) -> bool {
+        self.start.0 == self.end.0
+    }
+
+    /// t if the segment is at exactly 45°.
+    pub fn is_diagonal(&self) -> bool {
+        (self.end.0 - self.start.0).abs() == (self.end.1 - self.start.1).abs()
+    }
+
+    /// The integer points of the segment, from start to end.
+    ///
+    /// For horizontal, vertical and 45° segments these are exactly
+    /// the integer points the segment crosses.  For other slopes
+    /// they're the points Bresenham's algorithm would draw: one per
+    /// step along the longer axis, as close to the segment as can be.
+    pub fn points(&self) -> Points {
+        let dx = (self.end.0 - self.start.0).abs();
+        let dy = -(self.end.1 - self.start.1).abs();
+        Points {
+            current: Some(self.start),
+            end: self.end,
+            dx,
+            dy,
+            step: (
+                (self.end.0 - self.start.0).signum(),
+                (self.end.1 - self.start.1).signum(),
+            ),
+            error: dx + dy,
+        }
+    }
+
+    /// t if `p` is inside the bounding box of the segment.  For
+    /// points aligned with the segment, that means on it.
+    fn spans(&self, p: Point) -> bool {
+        p.0 >= self.start.0.min(self.end.0)
+            && p.0 <= self.start.0.max(self.end.0)
+            && p.1 >= self.start.1.min(self.end.1)
+            && p.1 <= self.start.1.max(self.end.1)
+    }
+
+    /// t if `p` lies exactly on the segment.
+    pub fn contains(&self, p: Point) -> bool {
+        orientation(self.start, self.end, p) == Ordering::Equal && self.spans(p)
+    }
+
+    /// t if the two segments have at least one point in common,
+    /// integer or not.  Computed exactly, without any division.
+    pub fn intersects(&self, other: &Segment) -> bool {
+        let o1 = orientation(self.start, self.end, other.start);
+        let o2 = orientation(self.start, self.end, other.end);
+        let o3 = orientation(other.start, other.end, self.start);
+        let o4 = orientation(other.start, other.end, self.end);
+        if o1 != o2 && o3 != o4 && o1 != Ordering::Equal && o2 != Ordering::Equal {
+            return true;
+        }
+        (o1 == Ordering::Equal && self.spans(other.start))
+            || (o2 == Ordering::Equal && self.spans(other.end))
+            || (o3 == Ordering::Equal && other.spans(self.start))
+            || (o4 == Ordering::Equal && other.spans(self.end))
+    }
+}
+
+/// An iterator over the integer points of a segment.  Built by
+/// [`Segment::points`].
+pub struct Points {
+    current: Option<Point>,
+    end: Point,
+    dx: i64,
+    dy: i64,
+    step: (i64, i64),
+    error: i64,
+}
+
+impl Iterator for Points {
+    type Item = Point;
+
+    fn next(&mut self) -> Option<Point> {
+        let ret = self.current?;
+        if ret == self.end {
+            self.current = None;
+            return Some(ret);
+        }
+        let (mut x, mut y) = ret;
+        let e2 = 2 * self.error;
+        if e2 >= self.dy {
+            self.error += self.dy;
+            x += self.step.0;
+        }
+        if e2 <= self.dx {
+            self.error += self.dx;
+            y += self.step.1;
+        }
+        self.current = Some((x, y));
+        Some(ret)
+    }
+}
+
+#[test]
+fn test_parse() {
+    assert_eq!(
+        "12,29 -> 28,12".parse::<Segment>(),
+        Ok(Segment::new((12, 29), (28, 12)))
+    );
+    assert_eq!(
+        "-1,0->3,-4".parse::<Segment>(),
+        Ok(Segment::new((-1, 0), (3, -4)))
+    );
+    assert!(matches!(
+        "12,29 28,12".parse::<Segment>(),
+        Err(ParseError::BadFormat(_))
+    ));
+    assert!(matches!(
+        "12,a -> 28,12".parse::<Segment>(),
+        Err(ParseError::NotANumber(_))
+    ));
+}
+
+#[test]
+fn test_points() {
+    let points = |s: &str| s.parse::<Segment>().unwrap().points().collect::<Vec<_>>();
+    assert_eq!(points("1,1 -> 1,3"), vec![(1, 1), (1, 2), (1, 3)]);
+    assert_eq!(points("9,7 -> 7,9"), vec![(9, 7), (8, 8), (7, 9)]);
+    assert_eq!(points("2,2 -> 2,2"), vec![(2, 2)]);
+    assert_eq!(
+        points("0,0 -> 4,2"),
+        vec![(0, 0), (1, 1), (2, 1), (3, 2), (4, 2)]
+    );
+    // Every slope gives one point per step along the longer axis,
+    // each next to the previous one, ending on the end point.
+    for end in [(7, 3), (-3, 7), (-7, -2), (1, -6), (5, 0)] {
+        let s = Segment::new((0, 0), end);
+        let pts: Vec<Point> = s.points().collect();
+        assert_eq!(pts.len() as i64, end.0.abs().max(end.1.abs()) + 1);
+        assert_eq!(*pts.last().unwrap(), end);
+        for w in pts.windows(2) {
+            assert!((w[1].0 - w[0].0).abs() <= 1 && (w[1].1 - w[0].1).abs() <= 1);
+        }
+    }
+}
+
+#[test]
+fn test_intersects() {
+    let seg = |s: &str| s.parse::<Segment>().unwrap();
+    // Crossing at a non-integer point.
+    assert!(seg("0,0 -> 1,1").intersects(&seg("0,1 -> 1,0")));
+    // Touching at an end.
+    assert!(seg("0,0 -> 2,2").intersects(&seg("2,2 -> 5,0")));
+    // Overlapping and disjoint collinear segments.
+    assert!(seg("0,0 -> 4,0").intersects(&seg("3,0 -> 9,0")));
+    assert!(!seg("0,0 -> 2,0").intersects(&seg("3,0 -> 9,0")));
+    // Parallel.
+    assert!(!seg("0,0 -> 4,2").intersects(&seg("0,1 -> 4,3")));
+    // The lines cross, the segments don't.
+    assert!(!seg("0,0 -> 1,1").intersects(&seg("3,0 -> 2,1")));
+    // Large coordinates don't overflow.
+    let big = 3_000_000_000_000;
+    assert!(
+        Segment::new((-big, -big), (big, big)).intersects(&Segment::new((-big, big), (big, -big)))
+    );
+    assert!(seg("0,0 -> 4,2").contains((2, 1)));
+    assert!(!seg("0,0 -> 4,2").contains((1, 0)));
+}
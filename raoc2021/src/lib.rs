@@ -1,3 +1,11 @@
+pub mod bits;
+pub mod geom;
+pub mod matrix;
+pub mod ocr;
+pub mod render;
+pub mod sliding;
+pub mod term;
+
 use std::fs::File;
 use std::io;
 use std::io::BufRead;
@@ -64,6 +72,15 @@ impl<T: Copy> Vec2D<T> {
         Vec2D { vec, width, height }
     }
 
+    pub fn from_rows(rows: &[Vec<T>]) -> Vec2D<T> {
+        let height = rows.len();
+        let width = if height > 0 { rows[0].len() } else { 0 };
+        let vec: Vec<T> = rows.iter().flatten().copied().collect();
+        assert_eq!(vec.len(), width * height, "Rows must have the same length");
+
+        Vec2D { vec, width, height }
+    }
+
     pub fn safe_index(&self, x: isize, y: isize) -> Option<T> {
         if self.test_coords(x, y) {
             Some(self[(x, y)])
@@ -127,10 +144,6 @@ where
     Ok(io::BufReader::new(file).lines())
 }
 
-pub fn bools_to_bin_string(n: &[bool]) -> String {
-    n.iter().map(|x| if *x { '1' } else { '0' }).collect()
-}
-
 pub fn read_digit(c: char) -> u8 {
     match c {
         '0' => 0,
@@ -0,0 +1,92 @@
+//! Matrices of counts, for linear recurrences.
+//!
+//! Every function here takes a `modulus`.  If it's None, counts are
+//! exact, and the program panics if they overflow a u128.  Otherwise
+//! they're computed modulo `modulus`, which must be non-zero and is
+//! below 2^64, so that products of two residues still fit in a u128.
+//! `mul_add`, `vec_mul` and `mat_mul` expect their inputs to be
+//! reduced already; `vec_mul_pow` reduces its own.
+
+pub type Matrix = Vec<Vec<u128>>;
+
+/// a + b × c, modulo `modulus` if there's one.
+pub fn mul_add(a: u128, b: u128, c: u128, modulus: Option<u64>) -> u128 {
+    match modulus {
+        // All of a, b and c are < modulus < 2^64
+        Some(m) => (a + (b * c) % m as u128) % m as u128,
+        None => b
+            .checked_mul(c)
+            .and_then(|bc| a.checked_add(bc))
+            .expect("Overflow: use a modulus"),
+    }
+}
+
+/// The row vector `vector` times `matrix`.
+pub fn vec_mul(vector: &[u128], matrix: &[Vec<u128>], modulus: Option<u64>) -> Vec<u128> {
+    let mut ret = vec![0; matrix.first().map_or(0, Vec::len)];
+    for (i, v) in vector.iter().enumerate() {
+        for (j, m) in matrix[i].iter().enumerate() {
+            ret[j] = mul_add(ret[j], *v, *m, modulus);
+        }
+    }
+    ret
+}
+
+pub fn mat_mul(a: &[Vec<u128>], b: &[Vec<u128>], modulus: Option<u64>) -> Matrix {
+    a.iter().map(|row| vec_mul(row, b, modulus)).collect()
+}
+
+/// The row vector `vector` times `matrix` to the power of n, by
+/// repeated squaring.
+pub fn vec_mul_pow(
+    vector: &[u128],
+    matrix: &[Vec<u128>],
+    n: u64,
+    modulus: Option<u64>,
+) -> Vec<u128> {
+    assert_ne!(modulus, Some(0), "The modulus can't be zero");
+    let reduce = |v: &u128| match modulus {
+        Some(m) => v % m as u128,
+        None => *v,
+    };
+    let mut ret: Vec<u128> = vector.iter().map(reduce).collect();
+    let mut power: Matrix = matrix
+        .iter()
+        .map(|row| row.iter().map(reduce).collect())
+        .collect();
+    let mut n = n;
+    while n > 0 {
+        if n & 1 == 1 {
+            ret = vec_mul(&ret, &power, modulus);
+        }
+        n >>= 1;
+        if n > 0 {
+            power = mat_mul(&power, &power, modulus);
+        }
+    }
+    ret
+}
+
+#[test]
+fn test_vec_mul_pow() {
+    // Fibonacci
+    let matrix = vec![vec![1, 1], vec![1, 0]];
+    assert_eq!(vec_mul_pow(&[1, 0], &matrix, 10, None), vec![89, 55]);
+    assert_eq!(vec_mul_pow(&[1, 0], &matrix, 10, Some(10)), vec![9, 5]);
+    assert_eq!(vec_mul_pow(&[1, 0], &matrix, 0, None), vec![1, 0]);
+
+    // Entries larger than the modulus.
+    let big = vec![vec![u64::MAX as u128 + 12, 1], vec![1, 0]];
+    let m = 1_000_000_007;
+    let reduced = vec![vec![(u64::MAX as u128 + 12) % m as u128, 1], vec![1, 0]];
+    assert_eq!(
+        vec_mul_pow(&[1, 0], &big, 20, Some(m)),
+        vec_mul_pow(&[1, 0], &reduced, 20, Some(m))
+    );
+}
+
+#[test]
+#[should_panic(expected = "zero")]
+fn test_zero_modulus() {
+    vec_mul_pow(&[1], &[vec![1]], 1, Some(0));
+}
@@ -0,0 +1,150 @@
+//! Read text drawn with the Advent of Code block font.
+//!
+//! Letters are 4 pixels wide and 6 pixels high, separated by one
+//! blank column.  Only the capitals that have shown up in actual
+//! puzzles are known.
+
+use crate::Vec2D;
+use std::fmt;
+
+pub const GLYPH_WIDTH: usize = 4;
+pub const GLYPH_HEIGHT: usize = 6;
+
+/// Glyphs, as their rows concatenated from top to bottom.
+const GLYPHS: [(char, &str); 16] = [
+    ('A', ".##.#..##..######..##..#"),
+    ('B', "###.#..####.#..##..####."),
+    ('C', ".##.#..##...#...#..#.##."),
+    ('E', "#####...###.#...#...####"),
+    ('F', "#####...###.#...#...#..."),
+    ('G', ".##.#..##...#.###..#.###"),
+    ('H', "#..##..######..##..##..#"),
+    ('J', "..##...#...#...##..#.##."),
+    ('K', "#..##.#.##..#.#.#.#.#..#"),
+    ('L', "#...#...#...#...#...####"),
+    ('O', ".##.#..##..##..##..#.##."),
+    ('P', "###.#..##..####.#...#..."),
+    ('R', "###.#..##..####.#.#.#..#"),
+    ('S', ".####...#....##....####."),
+    ('U', "#..##..##..##..##..#.##."),
+    ('Z', "####...#..#..#..#...####"),
+];
+
+/// Some glyphs couldn't be read.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct OcrError {
+    /// The text read so far, with `?` in place of unknown glyphs.
+    pub partial: String,
+    /// The position and drawing of each unknown glyph.
+    pub unrecognised: Vec<(usize, String)>,
+}
+
+impl fmt::Display for OcrError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} unrecognised glyph(s) in \"{}\"",
+            self.unrecognised.len(),
+            self.partial
+        )?;
+        for (pos, glyph) in &self.unrecognised {
+            writeln!(f)?;
+            writeln!(f, "at {}:", pos)?;
+            for row in glyph.as_bytes().chunks(GLYPH_WIDTH) {
+                writeln!(f, "{}", String::from_utf8_lossy(row))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for OcrError {}
+
+/// Read the glyph whose top-left corner is at (x, y), as a string
+/// of `#` and `.`.  Pixels outside the grid are blank.
+fn glyph_at(grid: &Vec2D<bool>, x: isize, y: isize) -> String {
+    let mut ret = String::with_capacity(GLYPH_WIDTH * GLYPH_HEIGHT);
+    for dy in 0..GLYPH_HEIGHT as isize {
+        for dx in 0..GLYPH_WIDTH as isize {
+            ret.push(if grid.safe_index(x + dx, y + dy) == Some(true) {
+                '#'
+            } else {
+                '.'
+            });
+        }
+    }
+    ret
+}
+
+/// Read the line of text starting at the top-left corner of `grid`.
+/// The line ends with the rightmost lit pixel of its first six rows.
+pub fn read(grid: &Vec2D<bool>) -> Result<String, OcrError> {
+    let mut width = 0;
+    for y in 0..GLYPH_HEIGHT.min(grid.height()) as isize {
+        for x in 0..grid.width() as isize {
+            if grid[(x, y)] {
+                width = width.max(x as usize + 1);
+            }
+        }
+    }
+
+    let mut partial = String::new();
+    let mut unrecognised = vec![];
+    for pos in 0..width.div_ceil(GLYPH_WIDTH + 1) {
+        let glyph = glyph_at(grid, (pos * (GLYPH_WIDTH + 1)) as isize, 0);
+        match GLYPHS.iter().find(|(_, g)| *g == glyph) {
+            Some((c, _)) => partial.push(*c),
+            None => {
+                partial.push('?');
+                unrecognised.push((pos, glyph));
+            }
+        }
+    }
+
+    if unrecognised.is_empty() {
+        Ok(partial)
+    } else {
+        Err(OcrError {
+            partial,
+            unrecognised,
+        })
+    }
+}
+
+#[cfg(test)]
+fn render(text: &[&str]) -> Vec2D<bool> {
+    let rows: Vec<Vec<bool>> = text
+        .iter()
+        .map(|row| row.chars().map(|c| c == '#').collect())
+        .collect();
+    Vec2D::from_rows(&rows)
+}
+
+#[test]
+fn test_read() {
+    let grid = render(&[
+        ".##..###...##.......",
+        "#..#.#..#.#..#......",
+        "#..#.###..#.........",
+        "####.#..#.#.........",
+        "#..#.#..#.#..#......",
+        "#..#.###...##.......",
+    ]);
+    assert_eq!(read(&grid), Ok(String::from("ABC")));
+}
+
+#[test]
+fn test_read_unrecognised() {
+    let grid = render(&[
+        "#..#.#...",
+        "#..#.#...",
+        "####..#..",
+        "#..#...#.",
+        "#..#....#",
+        "#..#.....",
+    ]);
+    let err = read(&grid).unwrap_err();
+    assert_eq!(err.partial, "H?");
+    assert_eq!(err.unrecognised.len(), 1);
+    assert_eq!(err.unrecognised[0].0, 1);
+}
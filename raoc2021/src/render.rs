@@ -0,0 +1,165 @@
+//! Write grids to Netpbm images.
+//!
+//! PPM (P6) and PGM (P5) are trivial binary formats that most image
+//! viewers and converters understand, so we don't need any dependency
+//! to produce them.  A sequence of frames can be turned into an
+//! animation with, eg, `ffmpeg -i frame-%05d.ppm out.gif`, as long
+//! as they all have the same size: use [`pad`] to draw grids that
+//! grow or shrink on a fixed canvas.
+
+use crate::Vec2D;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+pub type Rgb = (u8, u8, u8);
+
+pub fn write_ppm<T, W: Write>(
+    out: &mut W,
+    grid: &Vec2D<T>,
+    colour: &dyn Fn(&T) -> Rgb,
+) -> io::Result<()> {
+    write!(out, "P6\n{} {}\n255\n", grid.width(), grid.height())?;
+    for cell in &grid.vec {
+        let (r, g, b) = colour(cell);
+        out.write_all(&[r, g, b])?;
+    }
+    Ok(())
+}
+
+pub fn write_pgm<T, W: Write>(
+    out: &mut W,
+    grid: &Vec2D<T>,
+    grey: &dyn Fn(&T) -> u8,
+) -> io::Result<()> {
+    write!(out, "P5\n{} {}\n255\n", grid.width(), grid.height())?;
+    let bytes: Vec<u8> = grid.vec.iter().map(grey).collect();
+    out.write_all(&bytes)
+}
+
+pub fn save_ppm<T, P: AsRef<Path>>(
+    path: P,
+    grid: &Vec2D<T>,
+    colour: &dyn Fn(&T) -> Rgb,
+) -> io::Result<()> {
+    let mut out = BufWriter::new(File::create(path)?);
+    write_ppm(&mut out, grid, colour)?;
+    out.flush()
+}
+
+pub fn save_pgm<T, P: AsRef<Path>>(
+    path: P,
+    grid: &Vec2D<T>,
+    grey: &dyn Fn(&T) -> u8,
+) -> io::Result<()> {
+    let mut out = BufWriter::new(File::create(path)?);
+    write_pgm(&mut out, grid, grey)?;
+    out.flush()
+}
+
+/// Copy `grid` onto a `width`×`height` canvas filled with `fill`,
+/// its top-left corner at `at`.  What falls outside is cut.
+pub fn pad<T: Copy>(
+    grid: &Vec2D<T>,
+    width: usize,
+    height: usize,
+    at: (isize, isize),
+    fill: T,
+) -> Vec2D<T> {
+    let mut ret = Vec2D::new(width, height, fill);
+    for (i, cell) in grid.vec.iter().enumerate() {
+        let (x, y) = grid.to_coords(i);
+        let (x, y) = (x + at.0, y + at.1);
+        if ret.test_coords(x, y) {
+            ret[(x, y)] = *cell;
+        }
+    }
+    ret
+}
+
+/// Writes a numbered sequence of frames (`prefix-00000.ppm`,
+/// `prefix-00001.ppm`…) to a directory.
+pub struct FrameWriter {
+    dir: PathBuf,
+    prefix: String,
+    count: usize,
+}
+
+impl FrameWriter {
+    pub fn new<P: AsRef<Path>>(dir: P, prefix: &str) -> io::Result<FrameWriter> {
+        fs::create_dir_all(&dir)?;
+        Ok(FrameWriter {
+            dir: dir.as_ref().to_path_buf(),
+            prefix: prefix.to_string(),
+            count: 0,
+        })
+    }
+
+    /// Build a FrameWriter if the program was called with `--frames
+    /// DIR`.
+    pub fn from_args(prefix: &str) -> Option<FrameWriter> {
+        let mut args = std::env::args().skip_while(|a| a != "--frames").skip(1);
+        let dir = args.next()?;
+        let writer = FrameWriter::new(&dir, prefix)
+            .unwrap_or_else(|e| panic!("Can't write frames to {}: {}", dir, e));
+        Some(writer)
+    }
+
+    /// The number of frames written so far.
+    pub fn count(&self) -> usize {
+        self.count
+    }
+
+    fn next_path(&mut self, ext: &str) -> PathBuf {
+        let path = self
+            .dir
+            .join(format!("{}-{:05}.{}", self.prefix, self.count, ext));
+        self.count += 1;
+        path
+    }
+
+    pub fn ppm<T>(&mut self, grid: &Vec2D<T>, colour: &dyn Fn(&T) -> Rgb) -> io::Result<PathBuf> {
+        let path = self.next_path("ppm");
+        save_ppm(&path, grid, colour)?;
+        Ok(path)
+    }
+
+    pub fn pgm<T>(&mut self, grid: &Vec2D<T>, grey: &dyn Fn(&T) -> u8) -> io::Result<PathBuf> {
+        let path = self.next_path("pgm");
+        save_pgm(&path, grid, grey)?;
+        Ok(path)
+    }
+}
+
+#[test]
+fn test_write_ppm() {
+    let mut grid = Vec2D::new(2, 1, false);
+    grid[(1, 0)] = true;
+    let mut out = vec![];
+    write_ppm(&mut out, &grid, &|b| {
+        if *b {
+            (255, 0, 0)
+        } else {
+            (0, 0, 0)
+        }
+    })
+    .unwrap();
+    assert_eq!(out, b"P6\n2 1\n255\n\x00\x00\x00\xff\x00\x00");
+}
+
+#[test]
+fn test_pad() {
+    let grid = Vec2D::new(2, 2, 1u8);
+    let padded = pad(&grid, 3, 4, (1, 2), 0);
+    assert_eq!(padded.vec, vec![0, 0, 0, 0, 0, 0, 0, 1, 1, 0, 1, 1]);
+    let cut = pad(&grid, 2, 2, (-1, 1), 0);
+    assert_eq!(cut.vec, vec![0, 0, 1, 0]);
+}
+
+#[test]
+fn test_write_pgm() {
+    let grid = Vec2D::new(1, 2, 7u8);
+    let mut out = vec![];
+    write_pgm(&mut out, &grid, &|v| v * 10).unwrap();
+    assert_eq!(out, b"P5\n1 2\n255\nFF");
+}
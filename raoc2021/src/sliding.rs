@@ -0,0 +1,104 @@
+//! Sliding window sums over streams of numbers.
+
+use std::collections::VecDeque;
+
+/// An iterator over the differences between the sums of consecutive
+/// windows of an iterator.  Built by [`Increases::increases`].
+///
+/// Two consecutive windows share all their elements but the first
+/// of the former and the last of the latter, so the difference of
+/// their sums is just the difference of those: we only need to
+/// remember the last `window` elements.
+pub struct WindowDeltas<I> {
+    inner: I,
+    window: usize,
+    buffer: VecDeque<u32>,
+}
+
+impl<I: Iterator<Item = u32>> Iterator for WindowDeltas<I> {
+    type Item = i64;
+
+    fn next(&mut self) -> Option<i64> {
+        loop {
+            let new = self.inner.next()?;
+            self.buffer.push_back(new);
+            if self.buffer.len() > self.window {
+                let old = self.buffer.pop_front().unwrap();
+                return Some(new as i64 - old as i64);
+            }
+        }
+    }
+}
+
+/// Summary statistics of a stream of window deltas.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Summary {
+    /// How many times a window sum was larger than the previous one.
+    pub increases: usize,
+    /// The largest number of increases in a row.
+    pub longest_run: usize,
+    /// The largest decrease between two consecutive window sums, if
+    /// there's been any.
+    pub largest_drop: Option<u64>,
+}
+
+impl<I: Iterator<Item = u32>> WindowDeltas<I> {
+    pub fn summary(self) -> Summary {
+        let mut ret = Summary::default();
+        let mut run = 0;
+        for delta in self {
+            if delta > 0 {
+                ret.increases += 1;
+                run += 1;
+                ret.longest_run = ret.longest_run.max(run);
+            } else {
+                run = 0;
+            }
+            if delta < 0 {
+                let drop = delta.unsigned_abs();
+                ret.largest_drop = Some(ret.largest_drop.map_or(drop, |d| d.max(drop)));
+            }
+        }
+        ret
+    }
+}
+
+pub trait Increases: Iterator<Item = u32> + Sized {
+    /// Compare the sums of each window of `window` consecutive
+    /// elements with the previous one.  Inputs shorter than
+    /// `window + 1` elements just produce nothing.
+    fn increases(self, window: usize) -> WindowDeltas<Self> {
+        assert!(window > 0, "Windows can't be empty");
+        WindowDeltas {
+            inner: self,
+            window,
+            buffer: VecDeque::with_capacity(window + 1),
+        }
+    }
+}
+
+impl<I: Iterator<Item = u32>> Increases for I {}
+
+#[test]
+fn test_increases() {
+    let depths = [199, 200, 208, 210, 200, 207, 240, 269, 260, 263];
+    let a = depths.iter().copied().increases(1).summary();
+    assert_eq!(a.increases, 7);
+    assert_eq!(a.longest_run, 3);
+    assert_eq!(a.largest_drop, Some(10));
+    assert_eq!(depths.iter().copied().increases(3).summary().increases, 5);
+}
+
+#[test]
+fn test_increases_short() {
+    assert_eq!([1, 2].into_iter().increases(3).count(), 0);
+    assert_eq!([1, 2, 3].into_iter().increases(3).count(), 0);
+    assert_eq!(
+        std::iter::empty().increases(1).summary(),
+        Summary::default()
+    );
+    assert_eq!(
+        [5, 1, 2, 9].into_iter().increases(3).collect::<Vec<_>>(),
+        vec![4]
+    );
+}
@@ -0,0 +1,72 @@
+//! Coloured terminal output.
+//!
+//! Colour is opt-in: programs only colour their output when called
+//! with `--color`, and even then fall back to plain text when stdout
+//! isn't a terminal (`--color=always` forces it, eg for `less -R`).
+
+use ansi_term::{Colour, Style};
+use std::fmt::Display;
+use std::io::IsTerminal;
+
+/// A cold-to-hot gradient in the 256-colour palette.
+const HEAT: [u8; 12] = [21, 27, 33, 39, 45, 49, 46, 118, 190, 220, 208, 196];
+
+#[derive(Clone, Copy, Debug)]
+pub struct Painter {
+    enabled: bool,
+}
+
+impl Painter {
+    pub fn new(enabled: bool) -> Painter {
+        Painter { enabled }
+    }
+
+    pub fn from_args() -> Painter {
+        let mut enabled = false;
+        for arg in std::env::args().skip(1) {
+            match arg.as_str() {
+                "--color" => enabled = std::io::stdout().is_terminal(),
+                "--color=always" => enabled = true,
+                "--color=never" => enabled = false,
+                _ => {}
+            }
+        }
+        Painter { enabled }
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Render `s` in `style`, or as is if colour is disabled.
+    pub fn paint<S: Into<Style>, D: Display>(&self, style: S, s: D) -> String {
+        if self.enabled {
+            style.into().paint(s.to_string()).to_string()
+        } else {
+            s.to_string()
+        }
+    }
+}
+
+/// Map `value` in `0..=max` to a colour, from blue (cold) to red
+/// (hot).
+pub fn heat(value: u32, max: u32) -> Colour {
+    let max = max.max(1);
+    let i = (value.min(max) as usize * (HEAT.len() - 1)) / max as usize;
+    Colour::Fixed(HEAT[i])
+}
+
+#[test]
+fn test_heat() {
+    assert_eq!(heat(0, 10), Colour::Fixed(HEAT[0]));
+    assert_eq!(heat(10, 10), Colour::Fixed(HEAT[HEAT.len() - 1]));
+    assert_eq!(heat(20, 10), Colour::Fixed(HEAT[HEAT.len() - 1]));
+    assert_eq!(heat(3, 0), Colour::Fixed(HEAT[HEAT.len() - 1]));
+}
+
+#[test]
+fn test_plain_painter() {
+    let painter = Painter::new(false);
+    assert_eq!(painter.paint(Colour::Red.bold(), 42), "42");
+    assert_ne!(Painter::new(true).paint(Colour::Red, 42), "42");
+}